@@ -0,0 +1,52 @@
+export type Diagnostic = {
+  severity: "warning" | "error";
+  message: string;
+  filePath: string | null;
+  /** 1-based line number */
+  line: number | null;
+  /** 1-based column number */
+  column: number | null;
+  helpText: string | null;
+};
+
+export type CompileOptions = {
+  /** Path of JSON configuration file, defaults to `.qlcrc.json` in the working directory */
+  configFile?: string;
+  /** Path of schema introspection JSON file */
+  schemaPath?: string;
+  /** Any other qlc command line arguments, ie `["--use-custom-scalars"]` */
+  args?: string[];
+};
+
+export type DirectoryCompile = {
+  /** False when any document failed to compile */
+  success: boolean;
+  diagnostics: Diagnostic[];
+};
+
+export type DocumentCompile = {
+  success: boolean;
+  /** The TypeScript that qlc would write for the document, null when it failed to compile */
+  output: string | null;
+  diagnostics: Diagnostic[];
+};
+
+/** Compiles every document under `directory`, writing TypeScript files like the qlc binary does */
+export function compileDirectory(
+  directory: string,
+  options?: CompileOptions,
+): Promise<DirectoryCompile>;
+
+/**
+ * Compiles the single document at `filename` without writing anything. Passing `contents`
+ * compiles them as if they were the document at `filename`, ie for a bundler's loader.
+ */
+export function compileDocument(
+  filename: string,
+  options?: CompileOptions & {
+    /** Root directory for import prefixes and the default schema path, defaults to the working directory */
+    rootDir?: string;
+    /** Document contents to compile instead of reading `filename` */
+    contents?: string;
+  },
+): Promise<DocumentCompile>;
@@ -0,0 +1,77 @@
+"use strict";
+
+const { spawn } = require("child_process");
+const path = require("path");
+const binaryPath = require("./index");
+
+function optionArgs(options) {
+  const args = ["--diagnostics-format=json"];
+  if (options.configFile) {
+    args.push("--config-file", options.configFile);
+  }
+  if (options.schemaPath) {
+    args.push("--schema-path", options.schemaPath);
+  }
+  return args.concat(options.args || []);
+}
+
+function run(args, input) {
+  return new Promise((resolve, reject) => {
+    const stdin = input === undefined ? "ignore" : "pipe";
+    const child = spawn(binaryPath, args, { stdio: [stdin, "pipe", "pipe"] });
+    if (input !== undefined) {
+      child.stdin.end(input);
+    }
+    const stdout = [];
+    const stderr = [];
+    child.stdout.on("data", (chunk) => stdout.push(chunk));
+    child.stderr.on("data", (chunk) => stderr.push(chunk));
+    child
+      .on("error", reject)
+      .on("close", (code) => {
+        resolve({
+          code,
+          stdout: Buffer.concat(stdout).toString("utf8"),
+          stderr: Buffer.concat(stderr).toString("utf8"),
+        });
+      });
+  });
+}
+
+function parseDiagnostics(text) {
+  const lines = text.split("\n").filter((line) => line.trim());
+  try {
+    return lines.map((line) => JSON.parse(line));
+  } catch (_) {
+    // Problems found before qlc knows the format, like bad arguments, are not JSON
+    throw new Error(`qlc failed:\n${text}`);
+  }
+}
+
+async function compileDirectory(directory, options = {}) {
+  // Absolute, so a directory named like a command such as `schema` is not run as one
+  const args = optionArgs(options).concat(path.resolve(directory));
+  const { code, stdout, stderr } = await run(args);
+  if (stderr.trim()) {
+    throw new Error(`qlc failed:\n${stderr}`);
+  }
+  const diagnostics = parseDiagnostics(stdout);
+  return { success: code === 0, diagnostics };
+}
+
+async function compileDocument(filename, options = {}) {
+  const args = ["compile"].concat(
+    optionArgs(options),
+    options.rootDir || process.cwd(),
+    "--filename",
+    filename,
+  );
+  if (options.contents !== undefined) {
+    args.push("--stdin");
+  }
+  const { code, stdout, stderr } = await run(args, options.contents);
+  const diagnostics = parseDiagnostics(stderr);
+  return { success: code === 0, output: stdout || null, diagnostics };
+}
+
+module.exports = { compileDirectory, compileDocument };
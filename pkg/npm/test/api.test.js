@@ -0,0 +1,65 @@
+"use strict";
+
+// Runs against a qlc binary built by cargo instead of the downloaded one, ie
+// `QLC_BINARY=target/debug/qlc npm test`
+const assert = require("assert");
+const fs = require("fs");
+const os = require("os");
+const path = require("path");
+const { test } = require("node:test");
+
+const binaryModulePath = require.resolve("../lib/index");
+require.cache[binaryModulePath] = {
+  id: binaryModulePath,
+  filename: binaryModulePath,
+  loaded: true,
+  exports: path.resolve(process.env.QLC_BINARY || path.join(__dirname, "../../../target/debug/qlc")),
+};
+const { compileDirectory, compileDocument } = require("../lib/api");
+
+const SCHEMA_PATH = path.join(
+  __dirname,
+  "../../../tests/fixtures/schema_generation/output/schema.json",
+);
+
+function tempDir() {
+  return fs.mkdtempSync(path.join(os.tmpdir(), "qlc-api-"));
+}
+
+test("compileDirectory compiles a relative directory named like a command", async () => {
+  const parentDir = tempDir();
+  const rootDir = path.join(parentDir, "schema");
+  fs.mkdirSync(rootDir);
+  fs.copyFileSync(SCHEMA_PATH, path.join(rootDir, "schema.json"));
+  fs.writeFileSync(
+    path.join(rootDir, "operator_query.graphql"),
+    "query OperatorQuery {\n  operator {\n    nickname\n  }\n}\n",
+  );
+  const workingDir = process.cwd();
+  process.chdir(parentDir);
+  try {
+    const { success, diagnostics } = await compileDirectory("schema");
+    assert.strictEqual(success, false);
+    assert.strictEqual(diagnostics.length, 1);
+    assert.strictEqual(diagnostics[0].severity, "error");
+    assert.match(diagnostics[0].message, /unknown field `nickname`/);
+    assert.strictEqual(diagnostics[0].line, 3);
+    assert.strictEqual(diagnostics[0].column, 5);
+  } finally {
+    process.chdir(workingDir);
+  }
+});
+
+test("compileDocument compiles unsaved contents", async () => {
+  const rootDir = tempDir();
+  const filename = path.join(rootDir, "operator_query.graphql");
+  const compile = await compileDocument(filename, {
+    rootDir,
+    schemaPath: SCHEMA_PATH,
+    contents: "query OperatorQuery {\n  operator {\n    firstName\n  }\n}\n",
+  });
+  assert.strictEqual(compile.success, true);
+  assert.deepStrictEqual(compile.diagnostics, []);
+  assert.match(compile.output, /readonly firstName: string;/);
+  assert.ok(!fs.existsSync(filename));
+});
@@ -1,12 +1,20 @@
-use crate::graphql::BottomTypeConfig;
-use clap::Parser;
+use crate::graphql::possible_types::DEFAULT_POSSIBLE_TYPES_FILE_NAME;
+use crate::graphql::type_policies::{DEFAULT_KEY_FIELD, DEFAULT_TYPE_POLICIES_FILE_NAME};
+use crate::graphql::{
+    BottomTypeConfig, DEFAULT_GLOBAL_TYPES_MODULE_NAME,
+    DEFAULT_TYPED_GRAPHQL_DOCUMENTNODE_MODULE_NAME,
+};
+use clap::{Args, Parser, Subcommand, ValueEnum};
 use colored::{control, Colorize};
 use graphql_parser::Pos;
 use serde::Deserialize;
+use std::collections::BTreeMap;
 use std::fmt;
 use std::fs::File;
-use std::io::{BufReader, Error as IOError};
+use std::io::{BufReader, Error as IOError, Write};
 use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
 use strsim::generic_damerau_levenshtein;
 
 #[derive(Debug)]
@@ -52,6 +60,11 @@ impl LocationInformation {
         self
     }
 
+    fn line_and_column(&self) -> (usize, usize) {
+        let line_number = self.line_number.parse().unwrap_or(0);
+        (line_number, self.column_number.unwrap_or(0))
+    }
+
     fn line_number_digits(&self) -> usize {
         self.line_number.len()
     }
@@ -154,6 +167,53 @@ impl fmt::Display for PrintableMessage {
 }
 
 impl PrintableMessage {
+    /// Warnings do not fail a compile, everything else does
+    pub fn is_warning(&self) -> bool {
+        matches!(self.level, MessageLevel::CompileWarning)
+    }
+
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+
+    pub fn file_path(&self) -> Option<&Path> {
+        self.source_information
+            .as_ref()
+            .map(|source_information| source_information.file_path.as_path())
+    }
+
+    /// 1-based line and column the message points at, if it points at a line
+    pub fn line_and_column(&self) -> Option<(usize, Option<usize>)> {
+        let location = self.source_information.as_ref()?.location.as_ref()?;
+        let (line_number, _) = location.line_and_column();
+        Some((line_number, location.column_number))
+    }
+
+    pub fn help_text(&self) -> Option<&str> {
+        self.source_information
+            .as_ref()?
+            .location
+            .as_ref()?
+            .help_text
+            .as_deref()
+    }
+
+    /// The message as a single line JSON object, for `--diagnostics-format=json`
+    pub fn to_json(&self) -> serde_json::Value {
+        let (line, column) = match self.line_and_column() {
+            Some((line, column)) => (Some(line), column),
+            None => (None, None),
+        };
+        serde_json::json!({
+            "severity": if self.is_warning() { "warning" } else { "error" },
+            "message": self.message,
+            "filePath": self.file_path().map(|path| path.display().to_string()),
+            "line": line,
+            "column": column,
+            "helpText": self.help_text(),
+        })
+    }
+
     pub fn new_compile_error_from_read_io_error(error: &IOError, path: &Path) -> Self {
         PrintableMessage::new_compile_error_from_io_error("read", error, path)
     }
@@ -278,29 +338,47 @@ impl PrintableMessage {
 }
 
 pub trait ExitInformation {
-    fn messages(&self) -> &[PrintableMessage];
+    fn into_messages(self) -> Vec<PrintableMessage>;
 }
 
 impl ExitInformation for Vec<PrintableMessage> {
-    fn messages(&self) -> &[PrintableMessage] {
+    fn into_messages(self) -> Vec<PrintableMessage> {
         self
     }
 }
 
 #[derive(Parser, Debug)]
-#[command(name = "QL Compiler", version)]
+#[command(name = "QL Compiler", version, args_conflicts_with_subcommands = true)]
 #[command(
     about = "QL Compiler (qlc) compiles type definitions from graphql and introspection JSON."
 )]
 struct CliArgs {
-    /// Directory to recursively compile
+    #[command(subcommand)]
+    command: Option<Command>,
+    /// Directory to recursively compile, one named like a command must be written as a path, ie `./schema`
     root_dir: Option<PathBuf>,
     /// Path of JSON configuration file
-    #[arg(short, long, value_name = "FILE_PATH")]
+    #[arg(short, long, value_name = "FILE_PATH", global = true)]
     config_file: Option<PathBuf>,
     /// Path of schema introspection JSON file (defaults to <ROOT_DIR>/schema.json)
-    #[arg(short, long, value_name = "FILE_PATH")]
+    #[arg(short, long, value_name = "FILE_PATH", global = true)]
     schema_path: Option<PathBuf>,
+    /// Sets how diagnostics are printed, `json` prints one object per line for other tools to read (defaults to `human`)
+    #[arg(long, value_name = "FORMAT", global = true)]
+    diagnostics_format: Option<DiagnosticsFormat>,
+    /// Directory to cache the processed schema and compiled documents in, to skip unchanged work on later runs
+    #[arg(long, value_name = "DIR", global = true)]
+    cache_dir: Option<PathBuf>,
+    /// Disables color output
+    #[arg(long, global = true)]
+    no_color: bool,
+    #[command(flatten)]
+    compile_args: CompileArgs,
+}
+
+/// Options of compiling documents, taken by the root directory and the commands that compile
+#[derive(Args, Debug, Default)]
+pub struct CompileArgs {
     /// Use custom schema defined scalar names for types instead of any type
     #[arg(long)]
     use_custom_scalars: bool,
@@ -316,18 +394,139 @@ struct CliArgs {
     /// Sets the module name that will be used for importing typed GraphQL document nodes, defaults to `@notarize/qlc-cli/typed-documentnode`
     #[arg(long, value_name = "MODULE_NAME")]
     typed_graphql_documentnode_module_name: Option<String>,
+    /// Writes the possible types of interfaces and unions for Apollo's `InMemoryCache`, for those the documents use or all of them
+    #[arg(long, value_name = "SCOPE")]
+    possible_types: Option<PossibleTypesScope>,
+    /// Sets the file name of the possible types module in the root directory, a `.json` name writes JSON (defaults to `possible-types.ts`)
+    #[arg(long, value_name = "FILE_NAME")]
+    possible_types_file_name: Option<String>,
+    /// Selects `__typename` on every selection set except the operation's own, matching what Apollo Client sends and caches, and emits one variant per concrete type like --exhaustive-typename-unions
+    #[arg(long)]
+    add_typename: bool,
+    /// Writes Apollo type policies with the key field of every object type, and turns on --warn-missing-key-fields unless `warnMissingKeyFields` is `false`
+    #[arg(long)]
+    type_policies: bool,
+    /// Sets the file name of the type policies module in the root directory (defaults to `type-policies.ts`)
+    #[arg(long, value_name = "FILE_NAME")]
+    type_policies_file_name: Option<String>,
+    /// Field that identifies objects in the cache, the first one an object type has is its key field (defaults to `id`, repeatable)
+    #[arg(long = "key-field", value_name = "FIELD_NAME")]
+    key_fields: Vec<String>,
+    /// Enables warnings for selections of objects that leave out their key field, which Apollo needs to normalize them
+    #[arg(long)]
+    warn_missing_key_fields: bool,
+    /// Always emits one variant per concrete type for abstract type selections, so `__typename` can be switched on exhaustively
+    #[arg(long)]
+    exhaustive_typename_unions: bool,
     /// Disable marking types as readonly
     #[arg(long)]
     disable_readonly_types: bool,
     /// Enables warnings for deprecated field usage
     #[arg(long)]
     show_deprecation_warnings: bool,
+    /// Fails documents with fragment spreads that can never apply or variables that are never used
+    #[arg(long)]
+    strict_validation: bool,
+    /// Removes `#import` lines for fragments that are never spread, instead of warning about them
+    #[arg(long)]
+    remove_unused_fragment_imports: bool,
+    /// Prints diagnostics sorted by file path and position once all files are compiled, instead of as each file finishes
+    #[arg(long)]
+    sort_diagnostics: bool,
+    /// Writes a JSON report of every schema field, enum value and input field the documents use, with where they use it
+    #[arg(long, value_name = "FILE_PATH")]
+    usage_report: Option<PathBuf>,
     /// Sets the number of threads (defaults to number of CPU cores)
     #[arg(long, value_name = "NUMBER")]
     num_threads: Option<usize>,
-    /// Disables color output
-    #[arg(long)]
-    no_color: bool,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// Runs a language server over stdio, for diagnostics, completion, hover and go to definition in editors
+    Lsp {
+        /// Directory of the documents the editor works on
+        root_dir: Option<PathBuf>,
+        #[command(flatten)]
+        compile_args: CompileArgs,
+    },
+    /// Compiles a single document, printing its TypeScript to stdout and diagnostics to stderr
+    Compile {
+        /// Directory that root directory imports of the document resolve to
+        root_dir: Option<PathBuf>,
+        /// Reads the document from stdin instead of from the filename, ie for unsaved editor buffers
+        #[arg(long)]
+        stdin: bool,
+        /// Path of the document to compile, `#import`s are resolved relative to it
+        #[arg(long, value_name = "FILE_PATH")]
+        filename: PathBuf,
+        #[command(flatten)]
+        compile_args: CompileArgs,
+    },
+    /// Works with the schema introspection JSON
+    Schema {
+        #[command(subcommand)]
+        command: SchemaCommand,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+pub enum SchemaCommand {
+    /// Downloads the schema introspection JSON from a GraphQL endpoint, sending `Authorization: Bearer $QLC_SCHEMA_TOKEN` when that is set
+    Fetch {
+        /// URL of the GraphQL endpoint (defaults to `schemaUrl` of the config file)
+        url: Option<String>,
+        /// Path to write the introspection JSON to (defaults to the schema path)
+        #[arg(short, long, value_name = "FILE_PATH")]
+        output: Option<PathBuf>,
+        /// Adds a header to the introspection request, after any `schemaHeaders` of the config file
+        #[arg(short = 'H', long = "header", value_name = "NAME: VALUE", value_parser = parse_header)]
+        headers: Vec<(String, String)>,
+    },
+    /// Lists the changes between two schema introspection JSON files, failing when any are breaking
+    Diff {
+        /// Path of the current schema introspection JSON file
+        old: PathBuf,
+        /// Path of the new schema introspection JSON file
+        new: PathBuf,
+        /// Also reports the documents under this directory that the new schema breaks or changes the generated types of
+        #[arg(long, value_name = "DIR")]
+        documents: Option<PathBuf>,
+    },
+    /// Prints the schema as SDL, with types and fields sorted by name
+    Print {
+        /// Path of the schema introspection JSON file (defaults to the schema path)
+        schema: Option<PathBuf>,
+        /// Path to write the SDL to instead of stdout
+        #[arg(short, long, value_name = "FILE_PATH")]
+        output: Option<PathBuf>,
+    },
+}
+
+fn parse_header(header: &str) -> Result<(String, String), String> {
+    match header.split_once(':') {
+        Some((name, value)) if !name.trim().is_empty() => {
+            Ok((name.trim().to_string(), value.trim().to_string()))
+        }
+        _ => Err(String::from("expected a header of the form `NAME: VALUE`")),
+    }
+}
+
+#[derive(ValueEnum, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum PossibleTypesScope {
+    /// Interfaces and unions the compiled documents select or spread on
+    Used,
+    /// Every interface and union of the schema
+    All,
+}
+
+#[derive(ValueEnum, Deserialize, Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum DiagnosticsFormat {
+    #[default]
+    Human,
+    Json,
 }
 
 /// User configured configuration from configuration file, if it exists
@@ -335,22 +534,54 @@ struct CliArgs {
 struct ConfigFileMatches {
     #[serde(rename(deserialize = "schemaFile"))]
     schema_path: Option<PathBuf>,
+    #[serde(rename(deserialize = "schemaUrl"))]
+    schema_url: Option<String>,
+    #[serde(rename(deserialize = "schemaHeaders"))]
+    schema_headers: Option<BTreeMap<String, String>>,
     #[serde(rename(deserialize = "useCustomScalars"))]
     use_custom_scalars: Option<bool>,
     #[serde(rename(deserialize = "disableReadonlyTypes"))]
     disable_readonly_types: Option<bool>,
+    #[serde(rename(deserialize = "possibleTypes"))]
+    possible_types: Option<PossibleTypesScope>,
+    #[serde(rename(deserialize = "possibleTypesFileName"))]
+    possible_types_file_name: Option<String>,
+    #[serde(rename(deserialize = "addTypename"))]
+    add_typename: Option<bool>,
+    #[serde(rename(deserialize = "exhaustiveTypenameUnions"))]
+    exhaustive_typename_unions: Option<bool>,
+    #[serde(rename(deserialize = "typePolicies"))]
+    type_policies: Option<bool>,
+    #[serde(rename(deserialize = "typePoliciesFileName"))]
+    type_policies_file_name: Option<String>,
+    #[serde(rename(deserialize = "keyFields"))]
+    key_fields: Option<Vec<String>>,
+    #[serde(rename(deserialize = "warnMissingKeyFields"))]
+    warn_missing_key_fields: Option<bool>,
     #[serde(rename(deserialize = "customScalarPrefix"))]
     custom_scalar_prefix: Option<String>,
     #[serde(rename(deserialize = "numThreads"))]
     num_threads: Option<usize>,
     #[serde(rename(deserialize = "showDeprecationWarnings"))]
     show_deprecation_warnings: Option<bool>,
+    #[serde(rename(deserialize = "strictValidation"))]
+    strict_validation: Option<bool>,
+    #[serde(rename(deserialize = "removeUnusedFragmentImports"))]
+    remove_unused_fragment_imports: Option<bool>,
+    #[serde(rename(deserialize = "sortDiagnostics"))]
+    sort_diagnostics: Option<bool>,
+    #[serde(rename(deserialize = "diagnosticsFormat"))]
+    diagnostics_format: Option<DiagnosticsFormat>,
     #[serde(rename(deserialize = "rootDirImportPrefix"))]
     root_dir_import_prefix: Option<String>,
     #[serde(rename(deserialize = "globalTypesModuleName"))]
     global_types_module_name: Option<String>,
     #[serde(rename(deserialize = "typedGraphqlDocumentnodeModuleName"))]
     typed_graphql_documentnode_module_name: Option<String>,
+    #[serde(rename(deserialize = "cacheDir"))]
+    cache_dir: Option<PathBuf>,
+    #[serde(rename(deserialize = "usageReport"))]
+    usage_report: Option<PathBuf>,
 }
 
 impl ConfigFileMatches {
@@ -360,15 +591,18 @@ impl ConfigFileMatches {
         match File::open(config_file_path) {
             Ok(file) => serde_json::from_reader(BufReader::new(file))
                 .map(|mut config: Self| {
-                    config.schema_path = config.schema_path.and_then(|schema_path| {
-                        if schema_path.is_absolute() {
-                            Some(schema_path)
+                    let relative_to_config_file = |path: PathBuf| {
+                        if path.is_absolute() {
+                            Some(path)
                         } else {
                             config_file_path
                                 .parent()
-                                .map(|parent_config_dir| parent_config_dir.join(schema_path))
+                                .map(|parent_config_dir| parent_config_dir.join(path))
                         }
-                    });
+                    };
+                    config.schema_path = config.schema_path.and_then(relative_to_config_file);
+                    config.cache_dir = config.cache_dir.and_then(relative_to_config_file);
+                    config.usage_report = config.usage_report.and_then(relative_to_config_file);
                     config
                 })
                 .map_err(|serde_error| {
@@ -394,21 +628,38 @@ impl ConfigFileMatches {
 /// User configured runtime configuration
 #[derive(Debug)]
 pub struct RuntimeConfig {
+    command: Option<Command>,
     root_dir: PathBuf,
     schema_path: PathBuf,
+    schema_url: Option<String>,
+    schema_headers: Vec<(String, String)>,
     show_deprecation_warnings: bool,
+    strict_validation: bool,
+    remove_unused_fragment_imports: bool,
+    sort_diagnostics: bool,
+    diagnostics_format: DiagnosticsFormat,
     use_custom_scalars: bool,
     disable_readonly_types: bool,
+    add_typename: bool,
+    exhaustive_typename_unions: bool,
+    possible_types: Option<PossibleTypesScope>,
+    possible_types_file_name: String,
+    type_policies: bool,
+    type_policies_file_name: String,
+    key_fields: Vec<String>,
+    warn_missing_key_fields: bool,
     custom_scalar_prefix: Option<String>,
     number_threads: usize,
     root_dir_import_prefix: Option<String>,
     global_types_module_name: String,
     typed_graphql_documentnode_module_name: String,
+    cache_dir: Option<PathBuf>,
+    usage_report: Option<PathBuf>,
 }
 
 impl RuntimeConfig {
     pub fn from_cli() -> Self {
-        let cli_args = CliArgs::parse();
+        let mut cli_args = CliArgs::parse();
 
         if cli_args.no_color {
             control::set_override(false);
@@ -419,44 +670,122 @@ impl RuntimeConfig {
                 print_exit_info(vec![config_error_message]);
             });
 
-        let root_dir = cli_args.root_dir.unwrap_or_else(|| PathBuf::from("."));
+        // Options follow the command, so commands take the root directory themselves
+        let root_dir = match &cli_args.command {
+            Some(Command::Lsp { root_dir, .. } | Command::Compile { root_dir, .. }) => {
+                root_dir.clone()
+            }
+            Some(Command::Schema {
+                command: SchemaCommand::Diff { documents, .. },
+            }) => documents.clone(),
+            _ => cli_args.root_dir,
+        }
+        .unwrap_or_else(|| PathBuf::from("."));
+        let compile_args = match &mut cli_args.command {
+            Some(Command::Lsp { compile_args, .. } | Command::Compile { compile_args, .. }) => {
+                std::mem::take(compile_args)
+            }
+            _ => cli_args.compile_args,
+        };
+        let type_policies =
+            compile_args.type_policies || config_file_args.type_policies.unwrap_or(false);
         let schema_path = cli_args
             .schema_path
             .or(config_file_args.schema_path)
             .unwrap_or_else(|| root_dir.join("schema.json"));
 
         RuntimeConfig {
+            command: cli_args.command,
             root_dir,
             schema_path,
-            show_deprecation_warnings: cli_args.show_deprecation_warnings
+            schema_url: config_file_args.schema_url,
+            schema_headers: config_file_args
+                .schema_headers
+                .unwrap_or_default()
+                .into_iter()
+                .collect(),
+            show_deprecation_warnings: compile_args.show_deprecation_warnings
                 || config_file_args.show_deprecation_warnings.unwrap_or(false),
-            use_custom_scalars: cli_args.use_custom_scalars
+            strict_validation: compile_args.strict_validation
+                || config_file_args.strict_validation.unwrap_or(false),
+            remove_unused_fragment_imports: compile_args.remove_unused_fragment_imports
+                || config_file_args
+                    .remove_unused_fragment_imports
+                    .unwrap_or(false),
+            sort_diagnostics: compile_args.sort_diagnostics
+                || config_file_args.sort_diagnostics.unwrap_or(false),
+            diagnostics_format: cli_args
+                .diagnostics_format
+                .or(config_file_args.diagnostics_format)
+                .unwrap_or_default(),
+            use_custom_scalars: compile_args.use_custom_scalars
                 || config_file_args.use_custom_scalars.unwrap_or(false),
-            disable_readonly_types: cli_args.disable_readonly_types
+            disable_readonly_types: compile_args.disable_readonly_types
                 || config_file_args.disable_readonly_types.unwrap_or(false),
-            custom_scalar_prefix: cli_args.custom_scalar_prefix.or_else(|| {
+            possible_types: compile_args
+                .possible_types
+                .or(config_file_args.possible_types),
+            possible_types_file_name: compile_args
+                .possible_types_file_name
+                .or(config_file_args.possible_types_file_name)
+                .unwrap_or_else(|| String::from(DEFAULT_POSSIBLE_TYPES_FILE_NAME)),
+            add_typename: compile_args.add_typename
+                || config_file_args.add_typename.unwrap_or(false),
+            exhaustive_typename_unions: compile_args.exhaustive_typename_unions
+                || config_file_args.exhaustive_typename_unions.unwrap_or(false),
+            type_policies,
+            type_policies_file_name: compile_args
+                .type_policies_file_name
+                .or(config_file_args.type_policies_file_name)
+                .unwrap_or_else(|| String::from(DEFAULT_TYPE_POLICIES_FILE_NAME)),
+            key_fields: Some(compile_args.key_fields)
+                .filter(|key_fields| !key_fields.is_empty())
+                .or(config_file_args.key_fields)
+                .unwrap_or_else(|| vec![String::from(DEFAULT_KEY_FIELD)]),
+            // Type policies rely on key fields being selected, so they only change the default
+            warn_missing_key_fields: compile_args.warn_missing_key_fields
+                || config_file_args
+                    .warn_missing_key_fields
+                    .unwrap_or(type_policies),
+            custom_scalar_prefix: compile_args.custom_scalar_prefix.or_else(|| {
                 config_file_args
                     .use_custom_scalars
                     .and(config_file_args.custom_scalar_prefix)
             }),
-            number_threads: cli_args
+            number_threads: compile_args
                 .num_threads
                 .or(config_file_args.num_threads)
                 .unwrap_or_else(|| std::cmp::min(num_cpus::get(), 8)),
-            root_dir_import_prefix: cli_args
+            root_dir_import_prefix: compile_args
                 .root_dir_import_prefix
                 .or(config_file_args.root_dir_import_prefix),
-            global_types_module_name: cli_args
+            global_types_module_name: compile_args
                 .global_types_module_name
                 .or(config_file_args.global_types_module_name)
-                .unwrap_or_else(|| String::from("graphql-globals")),
-            typed_graphql_documentnode_module_name: cli_args
+                .unwrap_or_else(|| String::from(DEFAULT_GLOBAL_TYPES_MODULE_NAME)),
+            typed_graphql_documentnode_module_name: compile_args
                 .typed_graphql_documentnode_module_name
                 .or(config_file_args.typed_graphql_documentnode_module_name)
-                .unwrap_or_else(|| String::from("@notarize/qlc-cli/typed-documentnode")),
+                .unwrap_or_else(|| String::from(DEFAULT_TYPED_GRAPHQL_DOCUMENTNODE_MODULE_NAME)),
+            cache_dir: cli_args.cache_dir.or(config_file_args.cache_dir),
+            usage_report: compile_args.usage_report.or(config_file_args.usage_report),
         }
     }
 
+    /// The subcommand to run instead of compiling the root directory, if any
+    pub fn command(&self) -> Option<&Command> {
+        self.command.as_ref()
+    }
+
+    pub fn cache_dir_path(&self) -> Option<PathBuf> {
+        self.cache_dir.clone()
+    }
+
+    /// Where to write the report of the schema the documents use, if anywhere
+    pub fn usage_report_path(&self) -> Option<&Path> {
+        self.usage_report.as_deref()
+    }
+
     pub fn root_dir_path(&self) -> PathBuf {
         self.root_dir.clone()
     }
@@ -465,6 +794,16 @@ impl RuntimeConfig {
         self.schema_path.clone()
     }
 
+    /// URL of the GraphQL endpoint to fetch the schema from
+    pub fn schema_url(&self) -> Option<&str> {
+        self.schema_url.as_deref()
+    }
+
+    /// Headers to send when fetching the schema
+    pub fn schema_headers(&self) -> &[(String, String)] {
+        &self.schema_headers
+    }
+
     pub fn bottom_type_config(&self) -> BottomTypeConfig {
         match (self.use_custom_scalars, &self.custom_scalar_prefix) {
             (false, _) => BottomTypeConfig::DefaultBottomType,
@@ -477,6 +816,22 @@ impl RuntimeConfig {
         self.show_deprecation_warnings
     }
 
+    pub fn strict_validation(&self) -> bool {
+        self.strict_validation
+    }
+
+    pub fn remove_unused_fragment_imports(&self) -> bool {
+        self.remove_unused_fragment_imports
+    }
+
+    pub fn sort_diagnostics(&self) -> bool {
+        self.sort_diagnostics
+    }
+
+    pub fn diagnostics_format(&self) -> DiagnosticsFormat {
+        self.diagnostics_format
+    }
+
     pub fn thread_count(&self) -> usize {
         self.number_threads
     }
@@ -496,6 +851,38 @@ impl RuntimeConfig {
     pub fn disable_readonly_types(&self) -> bool {
         self.disable_readonly_types
     }
+
+    /// Which abstract types go in the possible types module, `None` when there is no module
+    pub fn possible_types_scope(&self) -> Option<PossibleTypesScope> {
+        self.possible_types
+    }
+
+    pub fn possible_types_file_path(&self) -> PathBuf {
+        self.root_dir.join(&self.possible_types_file_name)
+    }
+
+    pub fn add_typename(&self) -> bool {
+        self.add_typename
+    }
+
+    pub fn exhaustive_typename_unions(&self) -> bool {
+        self.exhaustive_typename_unions
+    }
+
+    /// Where to write the type policies module, `None` when there is no module
+    pub fn type_policies_file_path(&self) -> Option<PathBuf> {
+        self.type_policies
+            .then(|| self.root_dir.join(&self.type_policies_file_name))
+    }
+
+    /// Candidate key field names, in order of preference
+    pub fn key_fields(&self) -> &[String] {
+        &self.key_fields
+    }
+
+    pub fn warn_missing_key_fields(&self) -> bool {
+        self.warn_missing_key_fields
+    }
 }
 
 pub fn similar_help_suggestions(
@@ -518,40 +905,124 @@ pub fn similar_help_suggestions(
     }
 }
 
-/// Prints the result of the program to the screen with process exiting.
-pub fn print_exit_info(exit_info: impl ExitInformation) -> ! {
-    let mut warning_count = 0;
-    let mut error_count = 0;
-    for msg in exit_info.messages() {
-        match msg.level {
-            MessageLevel::CompileWarning => {
-                warning_count += 1;
-            }
-            MessageLevel::CompileError | MessageLevel::ProgramError => {
-                error_count += 1;
+/// Prints messages as they are reported, or holds on to them until [`MessagePrinter::finish`] to
+/// print them sorted by file path and position, for output that is stable between runs.
+#[derive(Debug)]
+pub struct MessagePrinter {
+    sort_messages: bool,
+    format: DiagnosticsFormat,
+    to_stderr: bool,
+    held_messages: Mutex<Vec<PrintableMessage>>,
+    warning_count: AtomicUsize,
+    error_count: AtomicUsize,
+}
+
+impl MessagePrinter {
+    pub fn new(sort_messages: bool, format: DiagnosticsFormat) -> Self {
+        MessagePrinter {
+            sort_messages,
+            format,
+            to_stderr: false,
+            held_messages: Mutex::new(Vec::new()),
+            warning_count: AtomicUsize::new(0),
+            error_count: AtomicUsize::new(0),
+        }
+    }
+
+    /// Print to stderr instead of stdout, for when stdout is reserved for compiled output
+    pub fn on_stderr(mut self) -> Self {
+        self.to_stderr = true;
+        self
+    }
+
+    fn lock_output(&self) -> Box<dyn Write + '_> {
+        if self.to_stderr {
+            Box::new(std::io::stderr().lock())
+        } else {
+            Box::new(std::io::stdout().lock())
+        }
+    }
+
+    fn write_messages(&self, messages: Vec<PrintableMessage>) {
+        let mut output = self.lock_output();
+        for msg in messages {
+            let _ = match self.format {
+                DiagnosticsFormat::Human => writeln!(output, "{}\n", msg),
+                DiagnosticsFormat::Json => writeln!(output, "{}", msg.to_json()),
+            };
+        }
+    }
+
+    /// Report a batch of messages, such as all the messages of a single file. A batch is printed
+    /// together so that messages of files compiled on different threads do not interleave.
+    pub fn report(&self, messages: Vec<PrintableMessage>) {
+        if messages.is_empty() {
+            return;
+        }
+        for msg in &messages {
+            match msg.level {
+                MessageLevel::CompileWarning => &self.warning_count,
+                MessageLevel::CompileError | MessageLevel::ProgramError => &self.error_count,
             }
+            .fetch_add(1, Ordering::Relaxed);
+        }
+        if self.sort_messages {
+            self.held_messages.lock().unwrap().extend(messages);
+        } else {
+            self.write_messages(messages);
         }
-        println!("{}\n", msg);
-    }
-    let has_errors = error_count > 0;
-    if has_errors {
-        let plural = if error_count > 1 { "s" } else { "" };
-        println!(
-            "{}",
-            PrintableMessage::new_simple_compile_error(&format!(
-                "failure due to {error_count} error{plural}",
-            ))
-        );
-    }
-    if warning_count > 0 {
-        let plural = if error_count > 1 { "s" } else { "" };
-        println!(
-            "{}",
-            PrintableMessage::new_simple_compile_warning(&format!(
-                "{warning_count} warning{plural} emitted",
-            ))
-        );
-    }
-
-    std::process::exit(i32::from(has_errors));
+    }
+
+    /// Prints any held messages and the summary, then exits the process.
+    pub fn finish(self) -> ! {
+        let mut held_messages = std::mem::take(&mut *self.held_messages.lock().unwrap());
+        // Stable, so messages at the same position keep the order they were reported in
+        held_messages.sort_by_cached_key(|msg| {
+            msg.source_information.as_ref().map(|source_information| {
+                let position = source_information
+                    .location
+                    .as_ref()
+                    .map(LocationInformation::line_and_column);
+                (source_information.file_path.clone(), position)
+            })
+        });
+        self.write_messages(held_messages);
+        let error_count = self.error_count.load(Ordering::Relaxed);
+        // The summary is for people, tools can count the messages themselves
+        if self.format == DiagnosticsFormat::Human {
+            self.write_summary(self.warning_count.load(Ordering::Relaxed), error_count);
+        }
+        std::process::exit(i32::from(error_count > 0));
+    }
+
+    fn write_summary(&self, warning_count: usize, error_count: usize) {
+        let mut output = self.lock_output();
+        if error_count > 0 {
+            let plural = if error_count > 1 { "s" } else { "" };
+            let _ = writeln!(
+                output,
+                "{}",
+                PrintableMessage::new_simple_compile_error(&format!(
+                    "failure due to {error_count} error{plural}",
+                ))
+            );
+        }
+        if warning_count > 0 {
+            let plural = if warning_count > 1 { "s" } else { "" };
+            let _ = writeln!(
+                output,
+                "{}",
+                PrintableMessage::new_simple_compile_warning(&format!(
+                    "{warning_count} warning{plural} emitted",
+                ))
+            );
+        }
+    }
+}
+
+/// Prints the result of the program to the screen with process exiting.
+pub fn print_exit_info(exit_info: impl ExitInformation) -> ! {
+    let printer = MessagePrinter::new(false, DiagnosticsFormat::Human);
+    printer.report(exit_info.into_messages());
+    printer.finish();
 }
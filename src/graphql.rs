@@ -1,27 +1,184 @@
-use super::cli::{LocationInformation, PrintableMessage, RuntimeConfig};
+use super::cli::{similar_help_suggestions, LocationInformation, PrintableMessage, RuntimeConfig};
 use crate::typescript;
-use graphql_parser::query::{Definition, Document, FragmentDefinition, OperationDefinition};
+use compile_cache::CompileCache;
+use fragment_cache::{CachedImport, FragmentCache, ParsedImport};
+use graphql_parser::query::{Definition, Document, OperationDefinition};
+use graphql_parser::Pos;
+use ir::ImportedFragments;
 use schema::Schema;
-use std::collections::{HashMap, HashSet};
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::fs::File;
 use std::io::{BufReader, Read};
-use std::path::{Path, PathBuf};
+use std::path::{Component, Path, PathBuf};
+use std::sync::Arc;
 
+pub mod compile_cache;
+pub mod fragment_cache;
 pub mod ir;
+pub mod possible_types;
 pub mod schema;
+pub mod type_policies;
+pub mod usage;
+pub mod validation;
 pub mod variable;
 
-const IMPORT_START: &str = "#import \"";
+pub const IMPORT_START: &str = "#import \"";
+
+pub const DEFAULT_GLOBAL_TYPES_MODULE_NAME: &str = "graphql-globals";
+pub const DEFAULT_TYPED_GRAPHQL_DOCUMENTNODE_MODULE_NAME: &str =
+    "@notarize/qlc-cli/typed-documentnode";
 
 pub type ParsedTextType = String;
 
-#[derive(Debug)]
+#[derive(Debug, Default)]
 pub struct CompileReport {
     pub messages: Vec<PrintableMessage>,
     pub global_types_used: HashSet<String>,
+    /// The named operation or fragment this document defines, if it could be parsed
+    pub definition: Option<DefinitionSummary>,
+    pub unresolved_spreads: Vec<UnresolvedSpread>,
+    /// What of the schema the document uses, present once it compiles to the IR
+    pub schema_usages: Vec<usage::SchemaUsage>,
+    /// Interfaces and unions the document selects or spreads on, for the possible types module
+    pub abstract_types_used: HashSet<String>,
 }
 
+/// Name and location of the single definition of a document
 #[derive(Debug)]
+pub struct DefinitionSummary {
+    pub name: String,
+    pub is_fragment: bool,
+    pub path: PathBuf,
+    position: Pos,
+    location: LocationInformation,
+}
+
+impl DefinitionSummary {
+    fn from_definition(
+        definition: &Definition<'_, ParsedTextType>,
+        path: &Path,
+        contents: &str,
+    ) -> Option<Self> {
+        let (name, is_fragment, position) = match definition {
+            Definition::Fragment(fragment_def) => {
+                (fragment_def.name.clone(), true, fragment_def.position)
+            }
+            Definition::Operation(OperationDefinition::Query(query)) => {
+                (query.name.clone()?, false, query.position)
+            }
+            Definition::Operation(OperationDefinition::Mutation(mutation)) => {
+                (mutation.name.clone()?, false, mutation.position)
+            }
+            Definition::Operation(OperationDefinition::Subscription(subscription)) => {
+                (subscription.name.clone()?, false, subscription.position)
+            }
+            Definition::Operation(OperationDefinition::SelectionSet(_)) => return None,
+        };
+        Some(DefinitionSummary {
+            name,
+            is_fragment,
+            path: path.to_owned(),
+            position,
+            location: LocationInformation::new_from_contents_and_position(contents, &position),
+        })
+    }
+}
+
+/// Errors for every operation or fragment name that is defined by more than one document. Names
+/// end up as TypeScript type names and document keys in tooling, so they must be unique.
+pub fn duplicate_definition_messages(definitions: &[DefinitionSummary]) -> Vec<PrintableMessage> {
+    let mut definitions_by_name: BTreeMap<&str, Vec<&DefinitionSummary>> = BTreeMap::new();
+    for definition in definitions {
+        definitions_by_name
+            .entry(&definition.name)
+            .or_default()
+            .push(definition);
+    }
+    let mut messages = Vec::new();
+    for (name, mut same_name_definitions) in definitions_by_name {
+        if same_name_definitions.len() < 2 {
+            continue;
+        }
+        same_name_definitions.sort_by(|a, b| a.path.cmp(&b.path));
+        for definition in &same_name_definitions {
+            let others = same_name_definitions
+                .iter()
+                .filter(|other| other.path != definition.path)
+                .map(|other| {
+                    format!(
+                        "`{}:{}:{}`",
+                        other.path.display(),
+                        other.position.line,
+                        other.position.column
+                    )
+                })
+                .collect::<Vec<_>>()
+                .join(", ");
+            let mut location = definition.location.clone();
+            location.with_help_text(&format!(
+                "Operation and fragment names must be unique across all documents, but `{name}` is also defined at {others}."
+            ));
+            let mut message = PrintableMessage::new_simple_compile_error(&format!(
+                "duplicate definition name `{name}`"
+            ));
+            message.with_source_information(&definition.path, Some(location));
+            messages.push(message);
+        }
+    }
+    messages
+}
+
+/// A spread of a fragment that is not in scope, waiting on the rest of the tree to be compiled
+/// so that it can suggest where to import the fragment from
+#[derive(Debug)]
+pub struct UnresolvedSpread {
+    fragment_name: String,
+    document_path: PathBuf,
+    location: LocationInformation,
+    imported_fragment_names: Vec<String>,
+}
+
+impl UnresolvedSpread {
+    pub fn into_message(
+        self,
+        config: &CompileConfig,
+        fragment_index: &HashMap<String, Vec<PathBuf>>,
+    ) -> PrintableMessage {
+        let UnresolvedSpread {
+            fragment_name,
+            document_path,
+            mut location,
+            imported_fragment_names,
+        } = self;
+        let extra = match fragment_index.get(&fragment_name) {
+            Some(fragment_paths) => {
+                let mut import_lines: Vec<_> = fragment_paths
+                    .iter()
+                    .map(|fragment_path| {
+                        format!(
+                            "`{IMPORT_START}{}\"`",
+                            import_path_of_fragment(config, &document_path, fragment_path)
+                        )
+                    })
+                    .collect();
+                import_lines.sort();
+                format!(" It can be imported with {}.", import_lines.join(" or "))
+            }
+            None => similar_help_suggestions(&fragment_name, imported_fragment_names.into_iter())
+                .unwrap_or_else(|| " Did you forget to import it?".to_string()),
+        };
+        location.with_help_text(&format!(
+            "This fragment name doesn't appear to be in scope.{extra}"
+        ));
+        let mut message = PrintableMessage::new_simple_compile_error(&format!(
+            "unknown spread fragment name `{fragment_name}`"
+        ));
+        message.with_source_information(&document_path, Some(location));
+        message
+    }
+}
+
+#[derive(Debug, Clone)]
 pub enum BottomTypeConfig {
     DefaultBottomType,
     RealName,
@@ -32,23 +189,182 @@ pub enum BottomTypeConfig {
 pub struct CompileConfig {
     root_dir: PathBuf,
     show_deprecation_warnings: bool,
+    /// Fail on impossible fragment spreads and unused variables
+    strict_validation: bool,
+    remove_unused_fragment_imports: bool,
+    add_typename: bool,
+    /// Candidate key fields to warn about selections without, no warnings when empty
+    key_fields: Vec<String>,
     pub use_readonly_types: bool,
+    /// One variant per concrete type for abstract types, instead of grouping identical ones. Always
+    /// set along with `add_typename`.
+    pub exhaustive_typename_unions: bool,
     pub bottom_type_config: BottomTypeConfig,
     pub root_dir_import_prefix: Option<String>,
     pub global_types_module_name: String,
     pub typed_graphql_documentnode_module_name: String,
+    compile_cache: Option<CompileCache>,
+}
+
+/// Builds a [`CompileConfig`], starting from the same defaults as the command line
+#[derive(Debug)]
+pub struct CompileConfigBuilder {
+    root_dir: PathBuf,
+    show_deprecation_warnings: bool,
+    strict_validation: bool,
+    add_typename: bool,
+    key_fields: Vec<String>,
+    use_readonly_types: bool,
+    exhaustive_typename_unions: bool,
+    bottom_type_config: BottomTypeConfig,
+    root_dir_import_prefix: Option<String>,
+    global_types_module_name: String,
+    typed_graphql_documentnode_module_name: String,
+}
+
+impl CompileConfigBuilder {
+    /// `root_dir` is the directory that the root directory import prefix resolves to
+    pub fn new(root_dir: impl Into<PathBuf>) -> Self {
+        CompileConfigBuilder {
+            root_dir: root_dir.into(),
+            show_deprecation_warnings: false,
+            strict_validation: false,
+            add_typename: false,
+            key_fields: Vec::new(),
+            use_readonly_types: true,
+            exhaustive_typename_unions: false,
+            bottom_type_config: BottomTypeConfig::DefaultBottomType,
+            root_dir_import_prefix: None,
+            global_types_module_name: String::from(DEFAULT_GLOBAL_TYPES_MODULE_NAME),
+            typed_graphql_documentnode_module_name: String::from(
+                DEFAULT_TYPED_GRAPHQL_DOCUMENTNODE_MODULE_NAME,
+            ),
+        }
+    }
+
+    pub fn with_deprecation_warnings(&mut self, show_deprecation_warnings: bool) -> &mut Self {
+        self.show_deprecation_warnings = show_deprecation_warnings;
+        self
+    }
+
+    /// Fail documents with fragment spreads that can never apply or variables that are never used,
+    /// instead of letting the IR warn about the spreads
+    pub fn with_strict_validation(&mut self, strict_validation: bool) -> &mut Self {
+        self.strict_validation = strict_validation;
+        self
+    }
+
+    /// Select `__typename` on every selection set but the operation's own, the way Apollo Client
+    /// does before sending a document. Abstract types then get one variant per concrete type, as
+    /// with [`Self::with_exhaustive_typename_unions`], so `__typename` can be switched on.
+    pub fn with_add_typename(&mut self, add_typename: bool) -> &mut Self {
+        self.add_typename = add_typename;
+        self
+    }
+
+    /// Warn about selections of objects that leave out their key field, the first of `key_fields`
+    /// the object's type has
+    pub fn with_missing_key_field_warnings(&mut self, key_fields: &[String]) -> &mut Self {
+        self.key_fields = key_fields.to_vec();
+        self
+    }
+
+    pub fn with_readonly_types(&mut self, use_readonly_types: bool) -> &mut Self {
+        self.use_readonly_types = use_readonly_types;
+        self
+    }
+
+    /// Emit one variant per concrete type of an abstract type, even when their fields are the same
+    pub fn with_exhaustive_typename_unions(
+        &mut self,
+        exhaustive_typename_unions: bool,
+    ) -> &mut Self {
+        self.exhaustive_typename_unions = exhaustive_typename_unions;
+        self
+    }
+
+    /// Use the schema's names for custom scalars instead of `any`
+    pub fn with_custom_scalars(&mut self) -> &mut Self {
+        self.bottom_type_config = BottomTypeConfig::RealName;
+        self
+    }
+
+    /// Use the schema's names for custom scalars, prefixed with `prefix`, instead of `any`
+    pub fn with_custom_scalar_prefix(&mut self, prefix: &str) -> &mut Self {
+        self.bottom_type_config = BottomTypeConfig::RealNameWithPrefix(prefix.to_string());
+        self
+    }
+
+    pub fn with_root_dir_import_prefix(&mut self, prefix: &str) -> &mut Self {
+        self.root_dir_import_prefix = Some(prefix.to_string());
+        self
+    }
+
+    pub fn with_global_types_module_name(&mut self, module_name: &str) -> &mut Self {
+        self.global_types_module_name = module_name.to_string();
+        self
+    }
+
+    pub fn with_typed_graphql_documentnode_module_name(&mut self, module_name: &str) -> &mut Self {
+        self.typed_graphql_documentnode_module_name = module_name.to_string();
+        self
+    }
+
+    pub fn build(&self) -> CompileConfig {
+        CompileConfig {
+            root_dir: self.root_dir.clone(),
+            show_deprecation_warnings: self.show_deprecation_warnings,
+            strict_validation: self.strict_validation,
+            add_typename: self.add_typename,
+            key_fields: self.key_fields.clone(),
+            remove_unused_fragment_imports: false,
+            use_readonly_types: self.use_readonly_types,
+            exhaustive_typename_unions: self.exhaustive_typename_unions || self.add_typename,
+            bottom_type_config: self.bottom_type_config.clone(),
+            root_dir_import_prefix: self.root_dir_import_prefix.clone(),
+            global_types_module_name: self.global_types_module_name.clone(),
+            typed_graphql_documentnode_module_name: self
+                .typed_graphql_documentnode_module_name
+                .clone(),
+            compile_cache: None,
+        }
+    }
 }
 
 impl From<&RuntimeConfig> for CompileConfig {
     fn from(from: &RuntimeConfig) -> Self {
-        CompileConfig {
+        let mut config = CompileConfig {
             root_dir: from.root_dir_path(),
             use_readonly_types: !from.disable_readonly_types(),
+            exhaustive_typename_unions: from.exhaustive_typename_unions() || from.add_typename(),
             bottom_type_config: from.bottom_type_config(),
             show_deprecation_warnings: from.show_deprecation_warnings(),
+            strict_validation: from.strict_validation(),
+            add_typename: from.add_typename(),
+            key_fields: if from.warn_missing_key_fields() {
+                from.key_fields().to_vec()
+            } else {
+                Vec::new()
+            },
+            remove_unused_fragment_imports: from.remove_unused_fragment_imports(),
             root_dir_import_prefix: from.root_dir_import_prefix(),
             global_types_module_name: from.global_types_module_name(),
             typed_graphql_documentnode_module_name: from.typed_graphql_documentnode_module_name(),
+            compile_cache: None,
+        };
+        // The cache is keyed on the rest of the config, so it is created last
+        config.compile_cache = from
+            .cache_dir_path()
+            .and_then(|cache_dir| CompileCache::new(cache_dir, &from.schema_file_path(), &config));
+        config
+    }
+}
+
+impl CompileConfig {
+    /// Remove compile cache entries that no document used, once every document is compiled
+    pub fn prune_compile_cache(&self) {
+        if let Some(compile_cache) = &self.compile_cache {
+            compile_cache.prune();
         }
     }
 }
@@ -99,7 +415,7 @@ fn parse_graphql_file<'a>(
     Ok(parsed)
 }
 
-fn get_file_path_of_fragment(
+pub fn get_file_path_of_fragment(
     config: &CompileConfig,
     import_comment: &str,
     current_dir: &Path,
@@ -123,31 +439,185 @@ fn get_file_path_of_fragment(
         })
 }
 
+/// Lexically normalized path, so that different import spellings of the same file compare equal
+pub fn normalize_path(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => match normalized.components().next_back() {
+                Some(Component::Normal(_)) => {
+                    normalized.pop();
+                }
+                _ => normalized.push(component),
+            },
+            _ => normalized.push(component),
+        }
+    }
+    normalized
+}
+
+/// Canonical path of a file, so that symlinks and different spellings of it compare equal. Paths
+/// that cannot be resolved, such as missing files, fall back to their lexical normalization.
+pub fn canonical_path(path: &Path) -> PathBuf {
+    std::fs::canonicalize(path).unwrap_or_else(|_| normalize_path(path))
+}
+
+/// The path an `#import` line in `document_path` would use to refer to `fragment_path`, the
+/// inverse of `get_file_path_of_fragment`
+pub fn import_path_of_fragment(
+    config: &CompileConfig,
+    document_path: &Path,
+    fragment_path: &Path,
+) -> String {
+    let fragment_path = canonical_path(fragment_path);
+    let component_names = |path: &Path| {
+        path.components()
+            .map(|component| component.as_os_str().to_string_lossy().into_owned())
+            .collect::<Vec<_>>()
+    };
+    if let Some(root_dir_prefix) = config.root_dir_import_prefix.as_deref() {
+        if let Ok(from_root) = fragment_path.strip_prefix(canonical_path(&config.root_dir)) {
+            return format!("{root_dir_prefix}{}", component_names(from_root).join("/"));
+        }
+    }
+    let document_dir = canonical_path(document_path.parent().unwrap_or_else(|| Path::new("")));
+    let document_dir_names = component_names(&document_dir);
+    let fragment_path_names = component_names(&fragment_path);
+    let common_count = document_dir_names
+        .iter()
+        .zip(&fragment_path_names)
+        .take_while(|(document_name, fragment_name)| document_name == fragment_name)
+        .count();
+    let parent_count = document_dir_names.len() - common_count;
+    let relative_names = fragment_path_names[common_count..].join("/");
+    if parent_count == 0 {
+        format!("./{relative_names}")
+    } else {
+        format!("{}{relative_names}", "../".repeat(parent_count))
+    }
+}
+
+/// A single `#import` line along the chain of imports being followed
+#[derive(Debug)]
+struct ImportHop {
+    importing_file: PathBuf,
+    imported_file: PathBuf,
+    line_number: usize,
+    location: LocationInformation,
+}
+
+fn circular_import_message(import_chain: &[ImportHop]) -> PrintableMessage {
+    let root_file = &import_chain[0].importing_file;
+    let cycle = import_chain
+        .iter()
+        .map(|hop| {
+            let importing_file = normalize_path(&hop.importing_file);
+            format!("`{}:{}`", importing_file.display(), hop.line_number)
+        })
+        .chain(std::iter::once(format!(
+            "`{}`",
+            normalize_path(root_file).display()
+        )))
+        .collect::<Vec<_>>()
+        .join(" -> ");
+    let mut location = import_chain[0].location.clone();
+    location.with_help_text(&format!(
+        "This import leads back to this document through {cycle}."
+    ));
+    let mut message = PrintableMessage::new_simple_compile_error("circular fragment import");
+    message.with_source_information(root_file, Some(location));
+    message
+}
+
+/// An entry of a directory that the compiler walks
+#[derive(Debug)]
+pub enum GraphQlDirEntry {
+    Dir(PathBuf),
+    Document(PathBuf),
+}
+
+/// The subdirectories and `.graphql` files directly in `dir`, skipping everything else
+pub fn graphql_dir_entries(dir: &Path) -> Result<Vec<GraphQlDirEntry>, std::io::Error> {
+    let mut entries = Vec::new();
+    for io_entry in std::fs::read_dir(dir)? {
+        let entry = io_entry?;
+        let file_type = entry.file_type()?;
+        let path = entry.path();
+        if file_type.is_dir() {
+            entries.push(GraphQlDirEntry::Dir(path));
+        } else if file_type.is_file() && path.extension().is_some_and(|x| x == "graphql") {
+            entries.push(GraphQlDirEntry::Document(path));
+        }
+    }
+    Ok(entries)
+}
+
+/// Every `.graphql` file under `dir`, the same files the compiler would compile. Directories that
+/// cannot be read are skipped.
+pub fn graphql_file_paths(dir: &Path, paths: &mut Vec<PathBuf>) {
+    for entry in graphql_dir_entries(dir).unwrap_or_default() {
+        match entry {
+            GraphQlDirEntry::Dir(path) => graphql_file_paths(&path, paths),
+            GraphQlDirEntry::Document(path) => paths.push(path),
+        }
+    }
+}
+
+/// Iterator of the zero-based line index and text of every `#import` line in a document's header
+pub fn import_lines(contents: &str) -> impl Iterator<Item = (usize, &str)> {
+    contents
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        // Stop reading lines as soon as we find a non-comment, non-empty line
+        .take_while(|(_, line)| line.starts_with('#'))
+        .filter(|(_, line)| line.starts_with(IMPORT_START))
+}
+
 fn add_imported_fragments(
     config: &CompileConfig,
+    fragment_cache: &FragmentCache,
     current_file: &Path,
     current_file_contents: &str,
-    import_contents: &mut HashMap<PathBuf, (String, LocationInformation)>,
+    import_contents: &mut HashMap<PathBuf, (Arc<CachedImport>, LocationInformation)>,
+    import_chain: &mut Vec<ImportHop>,
     messages: &mut Vec<PrintableMessage>,
 ) {
+    let canonical_root_file = canonical_path(
+        import_chain
+            .first()
+            .map(|hop| hop.importing_file.as_path())
+            .unwrap_or(current_file),
+    );
     let mut current_dir = current_file.to_owned();
     current_dir.pop();
-    for (line_index, line) in current_file_contents.lines().enumerate() {
-        if line.is_empty() || line.trim().is_empty() {
+    for (line_index, line) in import_lines(current_file_contents) {
+        let location =
+            LocationInformation::new_from_line_and_column(line_index + 1, line, IMPORT_START.len());
+        let file_path = get_file_path_of_fragment(config, line, &current_dir);
+        let canonical_file_path = canonical_path(&file_path);
+        let hop = ImportHop {
+            importing_file: current_file.to_owned(),
+            imported_file: file_path.clone(),
+            line_number: line_index + 1,
+            location: location.clone(),
+        };
+        if canonical_file_path == canonical_root_file {
+            import_chain.push(hop);
+            messages.push(circular_import_message(import_chain));
+            import_chain.pop();
             continue;
         }
-        let doesnt_start_with_import = !line.starts_with(IMPORT_START);
-        if doesnt_start_with_import && line.starts_with('#') {
-            // We keep going for comments
+        let is_in_chain = import_chain
+            .iter()
+            .any(|hop| canonical_path(&hop.imported_file) == canonical_file_path);
+        if is_in_chain || import_contents.contains_key(&canonical_file_path) {
+            // Cycles that do not pass through this document are reported when compiling the
+            // documents that form them; files already read were followed the first time.
             continue;
-        } else if doesnt_start_with_import {
-            // Stop reading lines as soon as we find a non-comment, non-empty line
-            break;
         }
-        let location =
-            LocationInformation::new_from_line_and_column(line_index + 1, line, IMPORT_START.len());
-        let file_path = get_file_path_of_fragment(config, line, &current_dir);
-        let other_file_contents = match read_graphql_file(&file_path) {
+        let other_file = match fragment_cache.get_or_load(&file_path) {
             Ok(c) => c,
             Err(mut sub_message) => {
                 sub_message.with_source_information(current_file, Some(location));
@@ -155,116 +625,326 @@ fn add_imported_fragments(
                 continue;
             }
         };
+        import_chain.push(hop);
         add_imported_fragments(
             config,
+            fragment_cache,
             &file_path,
-            &other_file_contents,
+            &other_file.contents,
             import_contents,
+            import_chain,
             messages,
         );
-        import_contents.insert(file_path, (other_file_contents, location));
+        import_chain.pop();
+        import_contents.insert(canonical_file_path, (other_file, location));
     }
 }
 
-fn parse_foreign_fragments<'a>(
+fn parse_foreign_fragments(
     path: &Path,
-    imported_contents: &'a HashMap<PathBuf, (String, LocationInformation)>,
+    imported_contents: &HashMap<PathBuf, (Arc<CachedImport>, LocationInformation)>,
     messages: &mut Vec<PrintableMessage>,
-) -> HashMap<String, FragmentDefinition<'a, ParsedTextType>> {
+) -> (ImportedFragments<'static>, HashMap<PathBuf, String>) {
     let mut parsed_imported_fragments = HashMap::new();
-    for (other_path, (contents, location)) in imported_contents.iter() {
-        if let Ok(mut parsed) = parse_graphql_file(contents, other_path) {
-            // We already know there is exactly one definition since read_graphql_file
-            // throws away documents that contain more or less than one defintion.
-            for def in parsed.definitions.drain(0..1) {
-                match def {
-                    Definition::Fragment(f_def) => {
-                        let fragment_name = f_def.name.clone();
-                        parsed_imported_fragments.insert(fragment_name, f_def);
-                    }
-                    _ => {
-                        let mut message = PrintableMessage::new_simple_compile_error(&format!(
-                            "cannot import non-fragment GraphQL document `{}`",
-                            path.display()
-                        ));
-                        let mut location = location.clone();
-                        location.with_help_text("This document is not a fragment, and importing it is probably a mistake.");
-                        message.with_source_information(path, Some(location));
-                        messages.push(message);
-                    }
-                }
+    let mut fragment_names_by_file = HashMap::new();
+    for (other_path, (cached_import, location)) in imported_contents.iter() {
+        match &cached_import.parsed {
+            ParsedImport::Fragment(f_def) => {
+                let fragment_name = f_def.name.clone();
+                fragment_names_by_file.insert(other_path.clone(), fragment_name.clone());
+                parsed_imported_fragments.insert(fragment_name, Arc::clone(f_def));
+            }
+            ParsedImport::NotAFragment => {
+                let mut message = PrintableMessage::new_simple_compile_error(&format!(
+                    "cannot import non-fragment GraphQL document `{}`",
+                    path.display()
+                ));
+                let mut location = location.clone();
+                location.with_help_text(
+                    "This document is not a fragment, and importing it is probably a mistake.",
+                );
+                message.with_source_information(path, Some(location));
+                messages.push(message);
             }
+            ParsedImport::Unparseable => {}
         }
     }
-    parsed_imported_fragments
+    (parsed_imported_fragments, fragment_names_by_file)
 }
 
-pub fn compile_file(
+/// Direct `#import` lines of a document whose fragment is never spread in it, along with the
+/// name of that fragment
+fn unused_fragment_imports<'a>(
+    config: &CompileConfig,
+    path: &Path,
+    contents: &'a str,
+    fragment_names_by_file: &'a HashMap<PathBuf, String>,
+    used_imported_fragments: &HashSet<String>,
+) -> Vec<(usize, &'a str, &'a str)> {
+    let mut current_dir = path.to_owned();
+    current_dir.pop();
+    import_lines(contents)
+        .filter_map(|(line_index, line)| {
+            let file_path = get_file_path_of_fragment(config, line, &current_dir);
+            fragment_names_by_file
+                .get(&canonical_path(&file_path))
+                .filter(|fragment_name| !used_imported_fragments.contains(*fragment_name))
+                .map(|fragment_name| (line_index, line, fragment_name.as_str()))
+        })
+        .collect()
+}
+
+fn remove_lines(
     path: &Path,
+    contents: &str,
+    line_indices: &[usize],
+) -> Result<(), PrintableMessage> {
+    let kept_contents: String = contents
+        .split_inclusive('\n')
+        .enumerate()
+        .filter(|(line_index, _)| !line_indices.contains(line_index))
+        .map(|(_, line)| line)
+        .collect();
+    std::fs::write(path, kept_contents).map_err(|io_error| {
+        PrintableMessage::new_compile_error_from_write_io_error(&io_error, path)
+    })
+}
+
+/// A document compiled in memory, before its output is written or its unused imports are
+/// dealt with
+pub struct ContentsCompile {
+    pub report: CompileReport,
+    /// Present when the document compiled without errors
+    pub output: Option<typescript::Compile>,
+    /// Line index, line and fragment name of every `#import` whose fragment is never spread
+    pub unused_imports: Vec<(usize, String, String)>,
+}
+
+/// Compile the `contents` of the document at `path`. Imports are resolved relative to `path` and
+/// read through `fragment_cache`.
+pub fn compile_contents(
+    path: &Path,
+    contents: &str,
     config: &CompileConfig,
     schema: &Schema,
-) -> Result<CompileReport, Vec<PrintableMessage>> {
-    let contents = read_graphql_file(path).map_err(|e| vec![e])?;
-    let parsed = parse_graphql_file(&contents, path).map_err(|e| vec![e])?;
+    fragment_cache: &FragmentCache,
+) -> ContentsCompile {
+    let mut report = CompileReport::default();
+    let parsed = match parse_graphql_file(contents, path) {
+        Ok(parsed) => parsed.into_static(),
+        Err(message) => {
+            report.messages.push(message);
+            return ContentsCompile::from(report);
+        }
+    };
+    report.definition = DefinitionSummary::from_definition(&parsed.definitions[0], path, contents);
 
-    let mut messages = Vec::new();
+    let messages = &mut report.messages;
     let mut imported_contents = HashMap::new();
     add_imported_fragments(
         config,
+        fragment_cache,
         path,
-        &contents,
+        contents,
         &mut imported_contents,
-        &mut messages,
+        &mut Vec::new(),
+        messages,
     );
-    let parsed_imported_fragments =
-        parse_foreign_fragments(path, &imported_contents, &mut messages);
+    let (parsed_imported_fragments, fragment_names_by_file) =
+        parse_foreign_fragments(path, &imported_contents, messages);
 
-    let (op_ir, warnings) = match ir::Operation::compile(
+    if let Err(validation_errors) = validation::validate(
+        &parsed.definitions[0],
+        schema,
+        &parsed_imported_fragments,
+        config.strict_validation,
+    ) {
+        messages.extend(
+            validation_errors
+                .into_iter()
+                .map(|validation_error| PrintableMessage::from((contents, path, validation_error))),
+        );
+        return ContentsCompile::from(report);
+    }
+
+    let (mut op_ir, warnings) = match ir::Operation::compile(
         &parsed.definitions[0],
         schema,
         parsed_imported_fragments,
         config.show_deprecation_warnings,
+        config.add_typename,
+        &config.key_fields,
     ) {
         Ok(ir) => ir,
         Err((ir_errors, warnings)) => {
-            messages.extend(
-                ir_errors
+            for ir_error in ir_errors {
+                match ir_error {
+                    // These are reported once the whole tree has been seen, since the fragment
+                    // might be defined in a document that was never imported.
+                    ir::Error::UnknownFragment(
+                        fragment_name,
+                        position,
+                        imported_fragment_names,
+                    ) => {
+                        report.unresolved_spreads.push(UnresolvedSpread {
+                            fragment_name,
+                            document_path: path.to_owned(),
+                            location: LocationInformation::new_from_contents_and_position(
+                                contents, &position,
+                            ),
+                            imported_fragment_names,
+                        });
+                    }
+                    ir_error => report
+                        .messages
+                        .push(PrintableMessage::from((contents, path, ir_error))),
+                }
+            }
+            report.messages.extend(
+                warnings
                     .into_iter()
-                    .map(|ir_error| PrintableMessage::from((contents.as_ref(), path, ir_error)))
-                    .chain(warnings.into_iter().map(|ir_warning| {
-                        PrintableMessage::from((contents.as_ref(), path, ir_warning))
-                    })),
+                    .map(|ir_warning| PrintableMessage::from((contents, path, ir_warning))),
             );
-            return Err(messages);
+            return ContentsCompile::from(report);
         }
     };
 
     messages.extend(
         warnings
             .into_iter()
-            .map(|ir_warning| PrintableMessage::from((contents.as_ref(), path, ir_warning))),
+            .map(|ir_warning| PrintableMessage::from((contents, path, ir_warning))),
     );
 
+    report.schema_usages = std::mem::take(&mut op_ir.usages);
+    report.abstract_types_used = std::mem::take(&mut op_ir.abstract_types_used);
     let the_compile = match typescript::compile_ir(&op_ir, config, schema) {
         Ok(c) => c,
         Err(inner_message) => {
             messages.push(inner_message.into());
-            return Err(messages);
+            return ContentsCompile::from(report);
         }
     };
 
+    let unused_imports = unused_fragment_imports(
+        config,
+        path,
+        contents,
+        &fragment_names_by_file,
+        &op_ir.used_imported_fragments,
+    )
+    .into_iter()
+    .map(|(line_index, line, fragment_name)| {
+        (line_index, line.to_string(), fragment_name.to_string())
+    })
+    .collect();
+    ContentsCompile {
+        report,
+        output: Some(the_compile),
+        unused_imports,
+    }
+}
+
+impl From<CompileReport> for ContentsCompile {
+    fn from(report: CompileReport) -> Self {
+        ContentsCompile {
+            report,
+            output: None,
+            unused_imports: Vec::new(),
+        }
+    }
+}
+
+pub fn unused_import_message(
+    path: &Path,
+    line_index: usize,
+    line: &str,
+    fragment_name: &str,
+) -> PrintableMessage {
+    let mut location =
+        LocationInformation::new_from_line_and_column(line_index + 1, line, IMPORT_START.len());
+    location.with_help_text(&format!(
+        "`{fragment_name}` is never spread in this document, so this import can be removed."
+    ));
+    let mut message = PrintableMessage::new_simple_compile_warning("unused fragment import");
+    message.with_source_information(path, Some(location));
+    message
+}
+
+pub fn compile_file(
+    path: &Path,
+    config: &CompileConfig,
+    schema: &Schema,
+    fragment_cache: &FragmentCache,
+) -> CompileReport {
+    let contents = match read_graphql_file(path) {
+        Ok(contents) => contents,
+        Err(message) => {
+            let mut report = CompileReport::default();
+            report.messages.push(message);
+            return report;
+        }
+    };
+    let cache_key = config
+        .compile_cache
+        .as_ref()
+        .and_then(|cache| cache.key(config, fragment_cache, path, &contents));
+    if let Some((cache, key)) = config.compile_cache.as_ref().zip(cache_key) {
+        if let Some(cached_report) = cache.load(key, path, &contents) {
+            return cached_report;
+        }
+    }
+
+    let ContentsCompile {
+        mut report,
+        output,
+        unused_imports,
+    } = compile_contents(path, &contents, config, schema, fragment_cache);
+    let Some(the_compile) = output else {
+        return report;
+    };
+
     let output_path = path.with_extension("graphql.d.ts");
-    std::fs::write(&output_path, the_compile.contents).map_err(|io_error| {
-        vec![PrintableMessage::new_compile_error_from_write_io_error(
-            &io_error,
-            &output_path,
-        )]
-    })?;
+    if let Err(io_error) = std::fs::write(&output_path, &the_compile.contents) {
+        report
+            .messages
+            .push(PrintableMessage::new_compile_error_from_write_io_error(
+                &io_error,
+                &output_path,
+            ));
+        return report;
+    }
 
-    Ok(CompileReport {
-        messages,
-        global_types_used: the_compile.global_types_used,
-    })
+    if config.remove_unused_fragment_imports && !unused_imports.is_empty() {
+        let line_indices: Vec<_> = unused_imports.iter().map(|(index, _, _)| *index).collect();
+        if let Err(message) = remove_lines(path, &contents, &line_indices) {
+            report.messages.push(message);
+        }
+    } else {
+        report.messages.extend(
+            unused_imports
+                .iter()
+                .map(|(line_index, line, fragment_name)| {
+                    unused_import_message(path, *line_index, line, fragment_name)
+                }),
+        );
+    }
+
+    // Only clean compiles are cached, since messages would otherwise need to be replayed
+    if let Some((cache, key)) = config.compile_cache.as_ref().zip(cache_key) {
+        if report.messages.is_empty() && report.unresolved_spreads.is_empty() {
+            cache.store(
+                key,
+                &the_compile.contents,
+                &the_compile.global_types_used,
+                &report.abstract_types_used,
+                &report.schema_usages,
+                report.definition.as_ref(),
+            );
+        }
+    }
+
+    report.global_types_used = the_compile.global_types_used;
+    report
 }
 
 pub fn compile_global_types_file(
@@ -0,0 +1,350 @@
+//! Persistent on-disk cache of compiled documents, keyed by a hash of everything that can affect
+//! the output of a compile
+use super::fragment_cache::FragmentCache;
+use super::usage::{SchemaUsage, UsageKind};
+use super::{
+    get_file_path_of_fragment, import_lines, normalize_path, BottomTypeConfig, CompileConfig,
+    CompileReport, DefinitionSummary,
+};
+use crate::cli::{LocationInformation, PrintableMessage};
+use fnv::FnvHasher;
+use graphql_parser::Pos;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashSet};
+use std::hash::Hasher;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+/// Bump whenever `CacheEntry` or what goes into a key changes, so older entries miss instead of
+/// loading wrong
+const CACHE_FORMAT_VERSION: u64 = 3;
+
+/// Hasher for keys that outlive the process. Neither `DefaultHasher` nor the `Hash` impls of std
+/// types are stable between Rust releases, so this only takes bytes, each prefixed by its length.
+pub(crate) struct CacheKeyHasher(FnvHasher);
+
+impl CacheKeyHasher {
+    pub fn new() -> Self {
+        CacheKeyHasher(FnvHasher::default())
+    }
+
+    pub fn write_bytes(&mut self, bytes: &[u8]) {
+        self.write_u64(bytes.len() as u64);
+        self.0.write(bytes);
+    }
+
+    pub fn write_str(&mut self, value: &str) {
+        self.write_bytes(value.as_bytes());
+    }
+
+    pub fn write_path(&mut self, path: &Path) {
+        self.write_str(&path.to_string_lossy());
+    }
+
+    pub fn write_u64(&mut self, value: u64) {
+        self.0.write(&value.to_le_bytes());
+    }
+
+    pub fn write_bool(&mut self, value: bool) {
+        self.0.write(&[u8::from(value)]);
+    }
+
+    pub fn write_option_str(&mut self, value: Option<&str>) {
+        self.write_bool(value.is_some());
+        if let Some(value) = value {
+            self.write_str(value);
+        }
+    }
+
+    pub fn finish(&self) -> u64 {
+        self.0.finish()
+    }
+}
+
+#[derive(Debug)]
+pub struct CompileCache {
+    dir: PathBuf,
+    /// Hash of the cache format, the qlc version, the schema file and the compile config, shared
+    /// by every document
+    base_hash: u64,
+    /// Keys loaded or stored during this run, everything else is removed by `prune`
+    used_keys: Mutex<HashSet<u64>>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct CachedDefinition {
+    name: String,
+    is_fragment: bool,
+    line: usize,
+    column: usize,
+}
+
+#[derive(Serialize, Deserialize)]
+struct CachedSchemaUsage {
+    coordinate: String,
+    kind: UsageKind,
+    deprecated: bool,
+    deprecation_reason: Option<String>,
+    line: usize,
+    column: usize,
+}
+
+#[derive(Serialize, Deserialize)]
+struct CacheEntry {
+    output: String,
+    global_types_used: HashSet<String>,
+    abstract_types_used: HashSet<String>,
+    schema_usages: Vec<CachedSchemaUsage>,
+    definition: Option<CachedDefinition>,
+}
+
+impl CompileCache {
+    pub fn new(dir: PathBuf, schema_path: &Path, config: &CompileConfig) -> Option<Self> {
+        let schema_contents = std::fs::read(schema_path).ok()?;
+        let mut hasher = CacheKeyHasher::new();
+        hasher.write_u64(CACHE_FORMAT_VERSION);
+        hasher.write_str(env!("CARGO_PKG_VERSION"));
+        hasher.write_bytes(&schema_contents);
+        hash_config(config, &mut hasher);
+        Some(CompileCache {
+            dir,
+            base_hash: hasher.finish(),
+            used_keys: Mutex::new(HashSet::new()),
+        })
+    }
+
+    /// Key for a document, covering its contents and the contents of all the fragments it
+    /// transitively imports. `None` when an import cannot be read, since the compile is going to
+    /// report an error anyway.
+    pub fn key(
+        &self,
+        config: &CompileConfig,
+        fragment_cache: &FragmentCache,
+        path: &Path,
+        contents: &str,
+    ) -> Option<u64> {
+        let mut imports = BTreeMap::new();
+        collect_transitive_imports(config, fragment_cache, path, contents, &mut imports)?;
+        let mut hasher = CacheKeyHasher::new();
+        hasher.write_u64(self.base_hash);
+        hasher.write_path(&normalize_path(path));
+        hasher.write_str(contents);
+        for (import_path, import_contents) in imports {
+            hasher.write_path(&import_path);
+            hasher.write_str(&import_contents);
+        }
+        Some(hasher.finish())
+    }
+
+    fn entry_path(&self, key: u64) -> PathBuf {
+        self.dir.join(format!("{key:016x}.json"))
+    }
+
+    fn mark_used(&self, key: u64) {
+        self.used_keys.lock().unwrap().insert(key);
+    }
+
+    /// Report for a previously compiled document, making sure its output file is up to date
+    pub fn load(&self, key: u64, path: &Path, contents: &str) -> Option<CompileReport> {
+        let entry_contents = std::fs::read_to_string(self.entry_path(key)).ok()?;
+        let entry: CacheEntry = serde_json::from_str(&entry_contents).ok()?;
+        self.mark_used(key);
+
+        let mut report = CompileReport::default();
+        let output_path = path.with_extension("graphql.d.ts");
+        let output_is_current = std::fs::read_to_string(&output_path)
+            .map(|existing_output| existing_output == entry.output)
+            .unwrap_or(false);
+        if !output_is_current {
+            if let Err(io_error) = std::fs::write(&output_path, &entry.output) {
+                report
+                    .messages
+                    .push(PrintableMessage::new_compile_error_from_write_io_error(
+                        &io_error,
+                        &output_path,
+                    ));
+                return Some(report);
+            }
+        }
+
+        report.global_types_used = entry.global_types_used;
+        report.abstract_types_used = entry.abstract_types_used;
+        report.schema_usages = entry
+            .schema_usages
+            .into_iter()
+            .map(|usage| SchemaUsage {
+                coordinate: usage.coordinate,
+                kind: usage.kind,
+                deprecated: usage.deprecated,
+                deprecation_reason: usage.deprecation_reason,
+                position: Pos {
+                    line: usage.line,
+                    column: usage.column,
+                },
+            })
+            .collect();
+        report.definition = entry.definition.map(|definition| {
+            let position = Pos {
+                line: definition.line,
+                column: definition.column,
+            };
+            DefinitionSummary {
+                name: definition.name,
+                is_fragment: definition.is_fragment,
+                path: path.to_owned(),
+                position,
+                location: LocationInformation::new_from_contents_and_position(contents, &position),
+            }
+        });
+        Some(report)
+    }
+
+    /// Save a clean compile. Failing to write the cache is not worth failing the compile over,
+    /// the document is simply compiled again next time. Entries are written to a temporary file
+    /// first, so that a killed run cannot leave a truncated entry behind.
+    pub fn store(
+        &self,
+        key: u64,
+        output: &str,
+        global_types_used: &HashSet<String>,
+        abstract_types_used: &HashSet<String>,
+        schema_usages: &[SchemaUsage],
+        definition: Option<&DefinitionSummary>,
+    ) {
+        let entry = CacheEntry {
+            output: output.to_string(),
+            global_types_used: global_types_used.clone(),
+            abstract_types_used: abstract_types_used.clone(),
+            schema_usages: schema_usages
+                .iter()
+                .map(|usage| CachedSchemaUsage {
+                    coordinate: usage.coordinate.clone(),
+                    kind: usage.kind,
+                    deprecated: usage.deprecated,
+                    deprecation_reason: usage.deprecation_reason.clone(),
+                    line: usage.position.line,
+                    column: usage.position.column,
+                })
+                .collect(),
+            definition: definition.map(|definition| CachedDefinition {
+                name: definition.name.clone(),
+                is_fragment: definition.is_fragment,
+                line: definition.position.line,
+                column: definition.position.column,
+            }),
+        };
+        let Ok(serialized) = serde_json::to_string(&entry) else {
+            return;
+        };
+        self.mark_used(key);
+        let temp_path = self
+            .dir
+            .join(format!("{key:016x}.{}.tmp", std::process::id()));
+        let written = std::fs::create_dir_all(&self.dir)
+            .and_then(|_| std::fs::write(&temp_path, serialized))
+            .and_then(|_| std::fs::rename(&temp_path, self.entry_path(key)));
+        if written.is_err() {
+            let _ = std::fs::remove_file(&temp_path);
+        }
+    }
+
+    /// Remove the entries, and temporary files left by killed runs, that were not used during this
+    /// run, so the directory only holds entries for the documents as they are now. Only files
+    /// named the way this cache names them are touched.
+    pub fn prune(&self) {
+        let Ok(dir_entries) = std::fs::read_dir(&self.dir) else {
+            return;
+        };
+        let used_keys = self.used_keys.lock().unwrap();
+        for dir_entry in dir_entries.filter_map(Result::ok) {
+            let file_name = dir_entry.file_name();
+            let Some((key, rest)) = file_name.to_str().and_then(|name| name.split_once('.')) else {
+                continue;
+            };
+            let is_entry = rest == "json" || rest.ends_with(".tmp");
+            let Some(key) = parse_key(key).filter(|_| is_entry) else {
+                continue;
+            };
+            if rest != "json" || !used_keys.contains(&key) {
+                let _ = std::fs::remove_file(dir_entry.path());
+            }
+        }
+    }
+}
+
+fn parse_key(key: &str) -> Option<u64> {
+    if key.len() != 16 || !key.bytes().all(|byte| byte.is_ascii_hexdigit()) {
+        return None;
+    }
+    u64::from_str_radix(key, 16).ok()
+}
+
+/// Everything in the config that can change the output of a compile. The destructuring is
+/// exhaustive so that new fields have to be considered here.
+fn hash_config(config: &CompileConfig, hasher: &mut CacheKeyHasher) {
+    let CompileConfig {
+        root_dir,
+        show_deprecation_warnings,
+        strict_validation,
+        remove_unused_fragment_imports,
+        add_typename,
+        key_fields,
+        use_readonly_types,
+        exhaustive_typename_unions,
+        bottom_type_config,
+        root_dir_import_prefix,
+        global_types_module_name,
+        typed_graphql_documentnode_module_name,
+        compile_cache: _,
+    } = config;
+    hasher.write_path(root_dir);
+    hasher.write_bool(*show_deprecation_warnings);
+    hasher.write_bool(*strict_validation);
+    hasher.write_bool(*remove_unused_fragment_imports);
+    hasher.write_bool(*add_typename);
+    hasher.write_u64(key_fields.len() as u64);
+    for key_field in key_fields {
+        hasher.write_str(key_field);
+    }
+    hasher.write_bool(*use_readonly_types);
+    hasher.write_bool(*exhaustive_typename_unions);
+    match bottom_type_config {
+        BottomTypeConfig::DefaultBottomType => hasher.write_u64(0),
+        BottomTypeConfig::RealName => hasher.write_u64(1),
+        BottomTypeConfig::RealNameWithPrefix(prefix) => {
+            hasher.write_u64(2);
+            hasher.write_str(prefix);
+        }
+    }
+    hasher.write_option_str(root_dir_import_prefix.as_deref());
+    hasher.write_str(global_types_module_name);
+    hasher.write_str(typed_graphql_documentnode_module_name);
+}
+
+fn collect_transitive_imports(
+    config: &CompileConfig,
+    fragment_cache: &FragmentCache,
+    current_file: &Path,
+    current_file_contents: &str,
+    imports: &mut BTreeMap<PathBuf, String>,
+) -> Option<()> {
+    let mut current_dir = current_file.to_owned();
+    current_dir.pop();
+    for (_, line) in import_lines(current_file_contents) {
+        let file_path = get_file_path_of_fragment(config, line, &current_dir);
+        let normalized_file_path = normalize_path(&file_path);
+        if imports.contains_key(&normalized_file_path) {
+            continue;
+        }
+        let imported = fragment_cache.get_or_load(&file_path).ok()?;
+        imports.insert(normalized_file_path, imported.contents.clone());
+        collect_transitive_imports(
+            config,
+            fragment_cache,
+            &file_path,
+            &imported.contents,
+            imports,
+        )?;
+    }
+    Some(())
+}
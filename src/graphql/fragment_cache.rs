@@ -0,0 +1,80 @@
+//! Pool-wide cache of imported fragment documents, so that a fragment imported by many documents
+//! is only read and parsed once per run
+use super::{canonical_path, parse_graphql_file, read_graphql_file, ParsedTextType};
+use crate::cli::PrintableMessage;
+use graphql_parser::query::{Definition, FragmentDefinition};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+#[derive(Debug)]
+pub enum ParsedImport {
+    Fragment(Arc<FragmentDefinition<'static, ParsedTextType>>),
+    NotAFragment,
+    /// Parse errors are reported when the document itself is compiled, not by its importers
+    Unparseable,
+}
+
+#[derive(Debug)]
+pub struct CachedImport {
+    pub contents: String,
+    pub parsed: ParsedImport,
+}
+
+impl CachedImport {
+    /// Parse the contents of the document at `path`, whether read from disk or not
+    pub fn parse(contents: String, path: &Path) -> Self {
+        let parsed = match parse_graphql_file(&contents, path) {
+            Ok(document) => match document.into_static().definitions.pop() {
+                Some(Definition::Fragment(fragment_def)) => {
+                    ParsedImport::Fragment(Arc::new(fragment_def))
+                }
+                _ => ParsedImport::NotAFragment,
+            },
+            Err(_) => ParsedImport::Unparseable,
+        };
+        CachedImport { contents, parsed }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct FragmentCache {
+    imports: Mutex<HashMap<PathBuf, Arc<CachedImport>>>,
+}
+
+impl FragmentCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Replace the document at `path` with `import`, such as the unsaved contents of a document
+    /// open in an editor
+    pub fn insert(&self, path: &Path, import: Arc<CachedImport>) {
+        self.imports
+            .lock()
+            .unwrap()
+            .insert(canonical_path(path), import);
+    }
+
+    /// Forget the document at `path`, so the next import of it reads it again
+    pub fn remove(&self, path: &Path) {
+        self.imports.lock().unwrap().remove(&canonical_path(path));
+    }
+
+    /// Read and parse the document at `path`, or reuse the result of a previous call for the
+    /// same file, however its path is spelled. Read errors are not cached.
+    pub fn get_or_load(&self, path: &Path) -> Result<Arc<CachedImport>, PrintableMessage> {
+        let key = canonical_path(path);
+        if let Some(cached) = self.imports.lock().unwrap().get(&key) {
+            return Ok(Arc::clone(cached));
+        }
+
+        // The lock is not held while reading and parsing, so another thread might load the same
+        // document concurrently. That is harmless, the first one inserted wins.
+        let contents = read_graphql_file(path)?;
+        let loaded = Arc::new(CachedImport::parse(contents, path));
+        Ok(Arc::clone(
+            self.imports.lock().unwrap().entry(key).or_insert(loaded),
+        ))
+    }
+}
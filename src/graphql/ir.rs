@@ -2,13 +2,16 @@ use super::ParsedTextType;
 use crate::cli::{similar_help_suggestions, PrintableMessage};
 use crate::graphql::schema;
 use crate::graphql::schema::field as schema_field;
+use crate::graphql::type_policies;
+use crate::graphql::usage::{self, SchemaUsage, UsageKind};
 use crate::graphql::variable;
 use graphql_parser::query as parsed_query;
 use graphql_parser::Pos;
 use std::collections::hash_map::Entry;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::convert::{TryFrom, TryInto};
 use std::path::Path;
+use std::sync::Arc;
 
 #[derive(Debug)]
 pub enum Warning {
@@ -22,6 +25,12 @@ pub enum Warning {
         field_name: String,
         parent_type_name: String,
     },
+    MissingKeyField {
+        position: Pos,
+        field_name: String,
+        key_field: String,
+        type_names: Vec<String>,
+    },
 }
 
 impl From<(&str, &Path, Warning)> for PrintableMessage {
@@ -48,6 +57,18 @@ impl From<(&str, &Path, Warning)> for PrintableMessage {
                 &position,
                 None
             ),
+            Warning::MissingKeyField { position, field_name, key_field, type_names } => {
+                let type_names = type_names.join("`, `");
+                PrintableMessage::new_compile_warning(
+                    &format!("selection of `{field_name}` is missing key field `{key_field}` of `{type_names}`"),
+                    file_path,
+                    contents,
+                    &position,
+                    Some(&format!(
+                        "Apollo normalizes `{type_names}` objects by `{key_field}` in its cache, without it they are stored under their parent and can go stale. Select `{key_field}` here or in a spread fragment.",
+                    )),
+                )
+            }
         }
     }
 }
@@ -88,6 +109,7 @@ impl ForeignFragmentJumpState {
 #[derive(Debug)]
 pub enum Error {
     SelectionSetAsOperationUnsupported(Pos),
+    MissingRootOperationType(&'static str, Pos),
     UnknownFragment(String, Pos, Vec<String>),
     MissingTypeConditionOnInlineFragment(Pos),
     SelectionSetOnWrongType(String, Pos),
@@ -123,6 +145,20 @@ impl From<(&str, &Path, Error)> for PrintableMessage {
                     Some("QLC does not support a plain selection set as an operation."),
                 )
             }
+            Error::MissingRootOperationType(operation_type, position) => {
+                let help = if operation_type == "query" {
+                    String::from("This document is a query, but the schema's introspection JSON has no `queryType` and no type named `Query`.")
+                } else {
+                    format!("This document is a {operation_type}, but the schema does not declare a root operation type for {operation_type} operations.")
+                };
+                PrintableMessage::new_compile_error(
+                    &format!("schema has no {operation_type} root type"),
+                    file_path,
+                    contents,
+                    &position,
+                    Some(&help),
+                )
+            }
             Error::UnknownFragment(name, position, possible_spread_names) => {
                 let extra = similar_help_suggestions(&name, possible_spread_names.into_iter())
                     .unwrap_or_else(|| " Did you forget to import it?".to_string());
@@ -201,19 +237,65 @@ type Result<T> = std::result::Result<T, Error>;
 type ResultMany<T> = std::result::Result<T, Vec<Error>>;
 type OperationResult<'a> =
     std::result::Result<(Operation<'a>, Vec<Warning>), (Vec<Error>, Vec<Warning>)>;
-type ImportedFragments<'a> = HashMap<String, parsed_query::FragmentDefinition<'a, ParsedTextType>>;
+pub type ImportedFragments<'a> =
+    HashMap<String, Arc<parsed_query::FragmentDefinition<'a, ParsedTextType>>>;
 
 pub struct CompileContext<'a, 'b> {
     pub schema: &'a schema::Schema,
     show_deprecation_warnings: bool,
+    /// Select `__typename` on every selection set but the operation's own, as Apollo Client does
+    add_typename: bool,
+    /// Candidate key fields of objects, selections that leave out an object's key field are
+    /// warned about unless this is empty
+    key_fields: &'a [String],
     imported_fragments: ImportedFragments<'b>,
     warnings: std::cell::RefCell<Vec<Warning>>,
+    used_imported_fragments: std::cell::RefCell<HashSet<String>>,
+    usages: std::cell::RefCell<Vec<SchemaUsage>>,
+    abstract_types_used: std::cell::RefCell<HashSet<String>>,
 }
 
 impl<'a, 'b> CompileContext<'a, 'b> {
     fn push_warning(&self, warning: Warning) {
         self.warnings.borrow_mut().push(warning);
     }
+
+    fn mark_imported_fragment_used(&self, fragment_name: &str) {
+        self.used_imported_fragments
+            .borrow_mut()
+            .insert(fragment_name.to_string());
+    }
+
+    fn mark_abstract_type_used(&self, type_name: &str) {
+        self.abstract_types_used
+            .borrow_mut()
+            .insert(type_name.to_string());
+    }
+
+    /// Records the use of `field` of `parent_type_name`, and of any enum values and input fields
+    /// written in its arguments
+    fn record_field_usage(
+        &self,
+        parent_type_name: &str,
+        field: &schema_field::Field,
+        selection_field: &parsed_query::Field<'_, ParsedTextType>,
+    ) {
+        let mut usages = self.usages.borrow_mut();
+        let position = selection_field.position;
+        usages.push(SchemaUsage {
+            coordinate: format!("{parent_type_name}.{}", field.name),
+            kind: UsageKind::Field,
+            deprecated: field.deprecated,
+            deprecation_reason: field.deprecation_reason.clone(),
+            position,
+        });
+        for (argument_name, value) in &selection_field.arguments {
+            if let Some(argument) = field.arguments.iter().find(|a| &a.name == argument_name) {
+                let type_name = &argument.type_description.reveal_concrete().name;
+                usage::value_usages(self.schema, type_name, value, position, &mut usages);
+            }
+        }
+    }
 }
 
 // For a few conversions with ?
@@ -316,6 +398,16 @@ impl<'a, 'b> ComplexTraversal<'a> {
         jump_state: ForeignFragmentJumpState,
     ) -> Result<Self> {
         Self::try_from((context, spread_type_name, position)).map(|mut base| {
+            if context.schema.is_sub_type(spread_type_name, self.type_name) {
+                // Spreading a type this one implements matches every object this could be, the
+                // spread's own possible types are not needed to know that
+                base.concrete_objects = self
+                    .concrete_objects
+                    .keys()
+                    .map(|type_name| (*type_name, UniqueFields::new()))
+                    .collect();
+                return base;
+            }
             base.concrete_objects
                 .retain(|type_name, _| self.concrete_objects.contains_key(type_name));
             if !jump_state.is_fully_jumped() && base.concrete_objects.is_empty() {
@@ -333,6 +425,13 @@ impl<'a, 'b> ComplexTraversal<'a> {
         })
     }
 
+    fn insert_typename(&mut self) -> Result<()> {
+        match self.fields_lookup.get_key_value("__typename") {
+            Some((name, field)) => self.insert_terminal(name, name, field, name.as_str().into()),
+            None => Ok(()),
+        }
+    }
+
     fn insert_terminal(
         &mut self,
         alias: &'a str,
@@ -415,6 +514,7 @@ impl<'a, 'b> TryFrom<(&'a CompileContext<'a, 'b>, &'a str, Pos)> for ComplexTrav
             }
             schema::TypeDefinition::Interface(schema::InterfaceType { possible_types, .. })
             | schema::TypeDefinition::Union(schema::UnionType { possible_types, .. }) => {
+                context.mark_abstract_type_used(type_name);
                 possible_types
                     .iter()
                     .map(|possible_type| (possible_type.as_ref(), UniqueFields::new()))
@@ -485,17 +585,6 @@ pub struct ComplexCollection {
     pub possibilities: Vec<Complex>,
 }
 
-impl From<&str> for OperationKind {
-    fn from(name: &str) -> Self {
-        match name {
-            "Mutation" => OperationKind::Mutation,
-            "Subscription" => OperationKind::Subscription,
-            "Query" => OperationKind::Query,
-            _ => OperationKind::Fragment,
-        }
-    }
-}
-
 #[derive(Debug)]
 pub enum OperationKind {
     Query,
@@ -504,26 +593,51 @@ pub enum OperationKind {
     Fragment,
 }
 
+impl OperationKind {
+    fn keyword(&self) -> &'static str {
+        match self {
+            OperationKind::Query => "query",
+            OperationKind::Subscription => "subscription",
+            OperationKind::Mutation => "mutation",
+            OperationKind::Fragment => "fragment",
+        }
+    }
+}
+
 #[derive(Debug)]
 pub struct Operation<'a> {
     pub name: String,
     pub collection: ComplexCollection,
     pub variables: Option<Vec<variable::Variable<'a>>>,
     pub kind: OperationKind,
-}
-
-impl<'a, 'b> Operation<'a> {
-    pub fn compile(
-        definition: &'a parsed_query::Definition<'a, ParsedTextType>,
-        schema: &'b schema::Schema,
-        imported_fragments: ImportedFragments<'a>,
+    /// Names of the imported fragments spread directly in this document (not through another
+    /// imported fragment)
+    pub used_imported_fragments: HashSet<String>,
+    /// Schema fields, enum values and input fields this document uses directly
+    pub usages: Vec<SchemaUsage>,
+    /// Interfaces and unions selected or used as type conditions, including through fragments
+    pub abstract_types_used: HashSet<String>,
+}
+
+impl<'a> Operation<'a> {
+    pub fn compile<'b>(
+        definition: &'a parsed_query::Definition<'b, ParsedTextType>,
+        schema: &schema::Schema,
+        imported_fragments: ImportedFragments<'b>,
         show_deprecation_warnings: bool,
+        add_typename: bool,
+        key_fields: &[String],
     ) -> OperationResult<'a> {
         let context = CompileContext {
             schema,
             show_deprecation_warnings,
+            add_typename,
+            key_fields,
             imported_fragments,
             warnings: std::cell::RefCell::new(Vec::new()),
+            used_imported_fragments: std::cell::RefCell::new(HashSet::new()),
+            usages: std::cell::RefCell::new(Vec::new()),
+            abstract_types_used: std::cell::RefCell::new(HashSet::new()),
         };
         let operation = match definition {
             parsed_query::Definition::Operation(op_def) => {
@@ -551,6 +665,11 @@ impl<'a, 'b> Operation<'a> {
                 ) {
                     return Err((errors, context.warnings.into_inner()));
                 }
+                if context.add_typename {
+                    if let Err(error) = parent.insert_typename() {
+                        return Err((vec![error], context.warnings.into_inner()));
+                    }
+                }
 
                 let collection = match parent.try_into() {
                     Ok(c) => c,
@@ -561,6 +680,9 @@ impl<'a, 'b> Operation<'a> {
                     collection,
                     variables: None,
                     kind: OperationKind::Fragment,
+                    used_imported_fragments: context.used_imported_fragments.take(),
+                    usages: context.usages.take(),
+                    abstract_types_used: context.abstract_types_used.take(),
                 }
             }
         };
@@ -568,43 +690,49 @@ impl<'a, 'b> Operation<'a> {
     }
 }
 
-fn build_from_operation<'a>(
-    context: &CompileContext<'_, 'a>,
-    operation: &'a parsed_query::OperationDefinition<'a, ParsedTextType>,
+fn build_from_operation<'a, 'b>(
+    context: &CompileContext<'_, 'b>,
+    operation: &'a parsed_query::OperationDefinition<'b, ParsedTextType>,
     jump_state: ForeignFragmentJumpState,
 ) -> ResultMany<Operation<'a>> {
-    let (op_type_name, fallback_name, op_name, selection_set, var_defs, position) = match operation
-    {
-        parsed_query::OperationDefinition::Query(query) => (
-            "Query",
-            "Query",
-            &query.name,
-            &query.selection_set,
-            &query.variable_definitions,
-            query.position,
-        ),
-        parsed_query::OperationDefinition::Mutation(mutation) => (
-            "Mutation",
-            "Mutation",
-            &mutation.name,
-            &mutation.selection_set,
-            &mutation.variable_definitions,
-            mutation.position,
-        ),
-        parsed_query::OperationDefinition::Subscription(subscription) => (
-            "Query", // We look in query as our type for subscriptions
-            "Subscription",
-            &subscription.name,
-            &subscription.selection_set,
-            &subscription.variable_definitions,
-            subscription.position,
-        ),
-        parsed_query::OperationDefinition::SelectionSet(selection) => {
-            return Err(vec![Error::SelectionSetAsOperationUnsupported(
-                selection.span.0,
-            )]);
-        }
-    };
+    let schema = context.schema;
+    let (op_type_name, kind, fallback_name, op_name, selection_set, var_defs, position) =
+        match operation {
+            parsed_query::OperationDefinition::Query(query) => (
+                schema.query_type_name(),
+                OperationKind::Query,
+                "Query",
+                &query.name,
+                &query.selection_set,
+                &query.variable_definitions,
+                query.position,
+            ),
+            parsed_query::OperationDefinition::Mutation(mutation) => (
+                schema.mutation_type_name(),
+                OperationKind::Mutation,
+                "Mutation",
+                &mutation.name,
+                &mutation.selection_set,
+                &mutation.variable_definitions,
+                mutation.position,
+            ),
+            parsed_query::OperationDefinition::Subscription(subscription) => (
+                schema.subscription_type_name(),
+                OperationKind::Subscription,
+                "Subscription",
+                &subscription.name,
+                &subscription.selection_set,
+                &subscription.variable_definitions,
+                subscription.position,
+            ),
+            parsed_query::OperationDefinition::SelectionSet(selection) => {
+                return Err(vec![Error::SelectionSetAsOperationUnsupported(
+                    selection.span.0,
+                )]);
+            }
+        };
+    let op_type_name =
+        op_type_name.ok_or_else(|| Error::MissingRootOperationType(kind.keyword(), position))?;
     let mut parent = ComplexTraversal::try_from((context, op_type_name, position))?;
     collect_fields_from_selection_set(context, selection_set, &mut parent, jump_state)?;
     Ok(Operation {
@@ -614,7 +742,10 @@ fn build_from_operation<'a>(
             .unwrap_or_else(|| fallback_name.to_string()),
         collection: parent.try_into()?,
         variables: variable::try_build_variable_ir(context, var_defs).map_err(Error::Variable)?,
-        kind: OperationKind::from(op_type_name),
+        kind,
+        used_imported_fragments: context.used_imported_fragments.take(),
+        usages: context.usages.take(),
+        abstract_types_used: context.abstract_types_used.take(),
     })
 }
 
@@ -692,10 +823,20 @@ fn insert_field<'a, 'b>(
                 &mut sub_parent,
                 jump_state,
             )?;
+            if context.add_typename {
+                sub_parent.insert_typename()?;
+            }
+            if jump_state.is_local() && !context.key_fields.is_empty() {
+                warn_missing_key_fields(context, alias, selection_field.position, &sub_parent);
+            }
             traversal.insert_complex(alias, field_type_name, field, sub_parent)?;
         }
     };
 
+    if jump_state.is_local() && !name.starts_with("__") {
+        context.record_field_usage(traversal.type_name, field, selection_field);
+    }
+
     if context.show_deprecation_warnings && field.deprecated && jump_state.is_local() {
         context.push_warning(Warning::DeprecatedFieldUse {
             position: selection_field.position,
@@ -707,6 +848,50 @@ fn insert_field<'a, 'b>(
     Ok(())
 }
 
+/// Warns about the objects `traversal` could be that have a key field it does not select, directly
+/// or through fragments
+fn warn_missing_key_fields(
+    context: &CompileContext<'_, '_>,
+    alias: &str,
+    position: Pos,
+    traversal: &ComplexTraversal<'_>,
+) {
+    let mut missing_by_key_field: HashMap<&str, Vec<String>> = HashMap::new();
+    for (type_name, uniques) in &traversal.concrete_objects {
+        let Some(key_field) = context
+            .schema
+            .get_type_for_name(type_name)
+            .and_then(|schema_type| schema_type.definition.get_fields_lookup())
+            .and_then(|fields_lookup| type_policies::key_field(fields_lookup, context.key_fields))
+        else {
+            continue;
+        };
+        // Apollo identifies objects by response key, so the key field has to be selected under
+        // its own name
+        let is_selected = uniques
+            .collection
+            .iter()
+            .any(|((alias, _), (field, _))| *alias == key_field && field.name == key_field);
+        if !is_selected {
+            missing_by_key_field
+                .entry(key_field)
+                .or_default()
+                .push(type_name.to_string());
+        }
+    }
+    let mut missing: Vec<_> = missing_by_key_field.into_iter().collect();
+    missing.sort_unstable();
+    for (key_field, mut type_names) in missing {
+        type_names.sort_unstable();
+        context.push_warning(Warning::MissingKeyField {
+            position,
+            field_name: alias.to_string(),
+            key_field: key_field.to_string(),
+            type_names,
+        });
+    }
+}
+
 fn collect_fields_from_selection_set<'a, 'b>(
     context: &'a CompileContext<'a, 'b>,
     selection_set: &'a parsed_query::SelectionSet<'b, ParsedTextType>,
@@ -744,6 +929,9 @@ fn collect_fields_from_selection_set<'a, 'b>(
             parsed_query::Selection::FragmentSpread(spread) => {
                 match context.imported_fragments.get(&spread.fragment_name) {
                     Some(fragment_def) => {
+                        if jump_state.is_local() {
+                            context.mark_imported_fragment_used(&spread.fragment_name);
+                        }
                         let parsed_query::TypeCondition::On(ref type_name) =
                             fragment_def.type_condition;
                         (
@@ -0,0 +1,64 @@
+//! The possible types module, mapping interfaces and unions to their object types for Apollo's
+//! `InMemoryCache`
+use crate::cli::{PossibleTypesScope, PrintableMessage};
+use crate::graphql::schema::{Schema, TypeDefinition};
+use crate::typescript::HEADER;
+use std::collections::{BTreeMap, HashSet};
+use std::path::Path;
+
+pub const DEFAULT_POSSIBLE_TYPES_FILE_NAME: &str = "possible-types.ts";
+
+/// Sorted object type names of every interface and union in `scope`
+fn possible_types_map<'a>(
+    schema: &'a Schema,
+    scope: PossibleTypesScope,
+    abstract_types_used: &HashSet<String>,
+) -> BTreeMap<&'a str, Vec<&'a str>> {
+    let mut map = BTreeMap::new();
+    for name in schema.type_names() {
+        if scope == PossibleTypesScope::Used && !abstract_types_used.contains(name) {
+            continue;
+        }
+        let possible_types = match &schema.get_type_for_name(name).unwrap().definition {
+            TypeDefinition::Interface(interface_type) => &interface_type.possible_types,
+            TypeDefinition::Union(union_type) => &union_type.possible_types,
+            _ => continue,
+        };
+        let mut possible_types: Vec<_> = possible_types.iter().map(String::as_str).collect();
+        possible_types.sort_unstable();
+        map.insert(name.as_str(), possible_types);
+    }
+    map
+}
+
+/// Write the possible types module to `output_path`, as JSON when it has a `.json` extension and
+/// as a TypeScript module exporting the map otherwise
+pub fn compile_possible_types_file(
+    output_path: &Path,
+    schema: &Schema,
+    scope: PossibleTypesScope,
+    abstract_types_used: &HashSet<String>,
+) -> Result<(), PrintableMessage> {
+    let map = possible_types_map(schema, scope, abstract_types_used);
+    let contents = if output_path.extension().is_some_and(|ext| ext == "json") {
+        serde_json::to_string_pretty(&map).expect("possible types should serialize")
+    } else {
+        let entries: Vec<_> = map
+            .into_iter()
+            .map(|(name, possible_types)| {
+                let quoted: Vec<_> = possible_types
+                    .into_iter()
+                    .map(|possible_type| format!("\"{possible_type}\""))
+                    .collect();
+                format!("  {name}: [{}],\n", quoted.join(", "))
+            })
+            .collect();
+        format!(
+            "{HEADER}const possibleTypes: Record<string, string[]> = {{\n{}}};\n\nexport default possibleTypes;",
+            entries.concat()
+        )
+    };
+    std::fs::write(output_path, contents).map_err(|io_error| {
+        PrintableMessage::new_compile_error_from_write_io_error(&io_error, output_path)
+    })
+}
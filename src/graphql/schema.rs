@@ -1,18 +1,31 @@
 //! Produce consumable schema from introspection JSON
 use crate::cli::PrintableMessage;
+use crate::graphql::canonical_path;
+use crate::graphql::compile_cache::CacheKeyHasher;
+use bincode::Options;
+use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::convert::TryFrom;
-use std::fs::File;
-use std::io::{BufReader, Read};
-use std::path::Path;
+use std::fs;
+use std::path::{Path, PathBuf};
 
+pub mod diff;
+pub mod fetch;
 pub mod field;
 mod json;
+pub mod sdl;
 
 pub type FieldsLookup = HashMap<String, field::Field>;
 
 pub type Documentation = Option<String>;
 
+const PROCESSED_SCHEMA_PREFIX: &str = "schema-";
+const PROCESSED_SCHEMA_EXTENSION: &str = ".bin";
+/// Bump whenever `Schema` or anything it holds changes shape. bincode is not self-describing, so
+/// a processed schema of another shape could otherwise deserialize into the wrong values. The
+/// processed schema fixture of the tests stops reading back when the shape changes.
+const PROCESSED_SCHEMA_FORMAT_VERSION: u64 = 2;
+
 #[derive(Debug)]
 pub enum Error {
     MissingTypeOfForList,
@@ -26,37 +39,50 @@ pub enum Error {
     JsonParse(serde_json::Error),
 }
 
-#[derive(Debug)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct ObjectType {
     pub fields: FieldsLookup,
+    /// Names of the interfaces this object implements
+    pub interfaces: Vec<String>,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Serialize, Deserialize)]
+pub struct EnumValue {
+    pub name: String,
+    pub documentation: Documentation,
+    pub deprecated: bool,
+    pub deprecation_reason: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
 pub struct EnumType {
-    pub possible_values: Vec<String>,
+    /// In the order of the schema
+    pub values: Vec<EnumValue>,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct InterfaceType {
     pub name: String,
     pub fields: FieldsLookup,
     pub possible_types: Vec<String>,
+    /// Names of the interfaces this interface implements
+    pub interfaces: Vec<String>,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct InputObjectType {
     pub name: String,
     pub fields: FieldsLookup,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct UnionType {
     pub name: String,
     pub possible_types: Vec<String>,
     pub fields: FieldsLookup,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Serialize, Deserialize)]
 pub enum TypeDefinition {
     Object(ObjectType),
     Enum(EnumType),
@@ -76,6 +102,15 @@ impl TypeDefinition {
             _ => None,
         }
     }
+
+    /// Names of the interfaces an object or interface declares it implements
+    pub fn interfaces(&self) -> &[String] {
+        match self {
+            TypeDefinition::Object(object_type) => &object_type.interfaces,
+            TypeDefinition::Interface(interface_type) => &interface_type.interfaces,
+            _ => &[],
+        }
+    }
 }
 
 fn get_fields_for_complex(
@@ -101,7 +136,46 @@ fn flattened_complex_description(
     descriptions.map(|inner| inner.into_iter().map(|v| v.name).collect())
 }
 
-#[derive(Debug)]
+impl From<json::EnumValue> for EnumValue {
+    fn from(json: json::EnumValue) -> Self {
+        EnumValue {
+            name: json.name,
+            documentation: field::documentation_from_description(json.description),
+            deprecated: json.deprecated.unwrap_or(false),
+            deprecation_reason: json.deprecation_reason,
+        }
+    }
+}
+
+/// A directive the schema declares, including the built in ones like `@include`
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Directive {
+    pub name: String,
+    pub documentation: Documentation,
+    pub locations: Vec<String>,
+    pub arguments: Vec<field::FieldArgument>,
+    pub repeatable: bool,
+}
+
+impl TryFrom<json::Directive> for Directive {
+    type Error = Error;
+
+    fn try_from(json: json::Directive) -> Result<Self, Self::Error> {
+        Ok(Directive {
+            name: json.name,
+            documentation: field::documentation_from_description(json.description),
+            locations: json.locations,
+            arguments: json
+                .args
+                .into_iter()
+                .map(field::FieldArgument::try_from)
+                .collect::<Result<_, _>>()?,
+            repeatable: json.repeatable.unwrap_or(false),
+        })
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
 pub struct Type {
     pub documentation: Documentation,
     pub definition: TypeDefinition,
@@ -119,19 +193,24 @@ impl TryFrom<json::Type> for Type {
             kind,
             enum_values,
             possible_types,
+            interfaces,
         } = json;
         let definition = match kind.as_ref() {
             "OBJECT" => {
                 let json_fields = fields.ok_or(Error::FieldsMissingForType(name))?;
                 let object_type = ObjectType {
                     fields: get_fields_for_complex(json_fields, true)?,
+                    interfaces: flattened_complex_description(interfaces).unwrap_or_default(),
                 };
                 TypeDefinition::Object(object_type)
             }
             "ENUM" => {
                 let enum_type = EnumType {
-                    possible_values: flattened_complex_description(enum_values)
-                        .ok_or(Error::EnumMissingValues(name))?,
+                    values: enum_values
+                        .ok_or(Error::EnumMissingValues(name))?
+                        .into_iter()
+                        .map(EnumValue::from)
+                        .collect(),
                 };
                 TypeDefinition::Enum(enum_type)
             }
@@ -145,6 +224,7 @@ impl TryFrom<json::Type> for Type {
                     name,
                     fields: get_fields_for_complex(json_fields, true)?,
                     possible_types,
+                    interfaces: flattened_complex_description(interfaces).unwrap_or_default(),
                 };
                 TypeDefinition::Interface(interface_type)
             }
@@ -178,36 +258,205 @@ impl TryFrom<json::Type> for Type {
     }
 }
 
+#[derive(Serialize, Deserialize)]
 pub struct Schema {
     types: HashMap<String, Type>,
+    query_type_name: Option<String>,
+    mutation_type_name: Option<String>,
+    subscription_type_name: Option<String>,
+    directives: Vec<Directive>,
 }
 
 impl Schema {
-    pub fn try_from_reader(reader: impl Read) -> Result<Self, Error> {
-        let schema_json = json::Schema::try_from_reader(reader).map_err(Error::JsonParse)?;
+    pub fn try_from_slice(contents: &[u8]) -> Result<Self, Error> {
+        let schema_json = json::Schema::try_from_slice(contents).map_err(Error::JsonParse)?;
         let mut types = HashMap::with_capacity(schema_json.types.len());
         for type_json in schema_json.types {
             types.insert(type_json.name.clone(), Type::try_from(type_json)?);
         }
-        Ok(Schema { types })
+        // Every schema has a query root, so introspection JSON without one, say written by hand,
+        // gets the conventional name like SDL without a schema definition
+        let query_type_name = schema_json
+            .query_type
+            .map(|root| root.name)
+            .or_else(|| types.contains_key("Query").then(|| String::from("Query")));
+        Ok(Schema {
+            types,
+            query_type_name,
+            mutation_type_name: schema_json.mutation_type.map(|root| root.name),
+            subscription_type_name: schema_json.subscription_type.map(|root| root.name),
+            directives: schema_json
+                .directives
+                .unwrap_or_default()
+                .into_iter()
+                .map(Directive::try_from)
+                .collect::<Result<_, _>>()?,
+        })
+    }
+
+    /// Name of the root type for query operations, if the schema declares one
+    pub fn query_type_name(&self) -> Option<&str> {
+        self.query_type_name.as_deref()
+    }
+
+    /// Name of the root type for mutation operations, if the schema declares one
+    pub fn mutation_type_name(&self) -> Option<&str> {
+        self.mutation_type_name.as_deref()
+    }
+
+    /// Name of the root type for subscription operations, if the schema declares one
+    pub fn subscription_type_name(&self) -> Option<&str> {
+        self.subscription_type_name.as_deref()
     }
 
     pub fn get_type_for_name(&self, name: &str) -> Option<&Type> {
         self.types.get(name)
     }
+
+    pub fn type_names(&self) -> impl Iterator<Item = &String> {
+        self.types.keys()
+    }
+
+    /// True when `type_name` is `abstract_type_name` itself, a member of the union, or a type that
+    /// declares it implements the interface. Interfaces count too, so a spread of an interface is
+    /// known to match on any interface that implements it.
+    pub fn is_sub_type(&self, abstract_type_name: &str, type_name: &str) -> bool {
+        if abstract_type_name == type_name {
+            return true;
+        }
+        match self
+            .get_type_for_name(abstract_type_name)
+            .map(|t| &t.definition)
+        {
+            Some(TypeDefinition::Union(union_type)) => union_type
+                .possible_types
+                .iter()
+                .any(|name| name == type_name),
+            Some(TypeDefinition::Interface(interface_type)) => {
+                interface_type
+                    .possible_types
+                    .iter()
+                    .any(|name| name == type_name)
+                    || self.get_type_for_name(type_name).is_some_and(|t| {
+                        t.definition
+                            .interfaces()
+                            .iter()
+                            .any(|name| name == abstract_type_name)
+                    })
+            }
+            _ => false,
+        }
+    }
+
+    pub fn directives(&self) -> &[Directive] {
+        &self.directives
+    }
 }
 
 fn printable_message_error(reason: &str) -> PrintableMessage {
     PrintableMessage::new_simple_compile_error(&format!("malformed schema: {reason}"))
 }
 
-pub fn parse_schema(path: &Path) -> Result<Schema, Vec<PrintableMessage>> {
-    let file = File::open(path).map_err(|io_error| {
+/// Path of the processed schema cached for the introspection JSON at `path`. The name is a hash of
+/// the path, so each schema has its own entry, then a hash of what the entry is valid for: the
+/// JSON, the version of qlc and the format of the processed schema.
+fn processed_schema_cache_path(cache_dir: &Path, path: &Path, contents: &[u8]) -> PathBuf {
+    let mut path_hasher = CacheKeyHasher::new();
+    path_hasher.write_str(&canonical_path(path).to_string_lossy());
+    let mut hasher = CacheKeyHasher::new();
+    hasher.write_u64(PROCESSED_SCHEMA_FORMAT_VERSION);
+    hasher.write_str(env!("CARGO_PKG_VERSION"));
+    hasher.write_bytes(contents);
+    cache_dir.join(format!(
+        "{PROCESSED_SCHEMA_PREFIX}{:016x}-{:016x}{PROCESSED_SCHEMA_EXTENSION}",
+        path_hasher.finish(),
+        hasher.finish()
+    ))
+}
+
+/// The same options for writing and reading, rejecting trailing bytes so that a processed schema
+/// of another shape is less likely to be read at all
+fn bincode_options() -> impl Options {
+    bincode::DefaultOptions::new()
+}
+
+fn read_processed_schema(cache_path: &Path) -> Option<Schema> {
+    let serialized = fs::read(cache_path).ok()?;
+    // Nothing in a valid processed schema is longer than the file, so a corrupt length cannot
+    // make bincode allocate more than that
+    bincode_options()
+        .with_limit(serialized.len() as u64)
+        .deserialize(&serialized)
+        .ok()
+}
+
+/// The hash of the schema path in a file name of exactly the shape of the processed schema file
+/// names this writes, so that nothing else in the cache directory is ever removed
+fn processed_schema_path_hash(file_name: &str) -> Option<&str> {
+    let (path_hash, hash) = file_name
+        .strip_prefix(PROCESSED_SCHEMA_PREFIX)?
+        .strip_suffix(PROCESSED_SCHEMA_EXTENSION)?
+        .split_once('-')?;
+    let is_hash =
+        |hash: &str| hash.len() == 16 && hash.bytes().all(|byte| byte.is_ascii_hexdigit());
+    (is_hash(path_hash) && is_hash(hash)).then_some(path_hash)
+}
+
+/// Failing to write the cache is not worth failing over, the JSON is simply processed again on
+/// the next run. Processed schemas for previous versions of the same JSON file are removed, those
+/// of other schemas sharing the cache directory are kept.
+fn write_processed_schema(cache_path: &Path, schema: &Schema) {
+    let (Some(cache_dir), Some(path_hash)) = (
+        cache_path.parent(),
+        cache_path
+            .file_name()
+            .and_then(|file_name| file_name.to_str())
+            .and_then(processed_schema_path_hash),
+    ) else {
+        return;
+    };
+    if let Ok(entries) = fs::read_dir(cache_dir) {
+        for entry in entries.filter_map(Result::ok) {
+            let file_name = entry.file_name();
+            if file_name
+                .to_str()
+                .and_then(processed_schema_path_hash)
+                .is_some_and(|entry_path_hash| entry_path_hash == path_hash)
+            {
+                let _ = fs::remove_file(entry.path());
+            }
+        }
+    }
+    if let Ok(serialized) = bincode_options().serialize(schema) {
+        let _ = fs::create_dir_all(cache_dir).and_then(|_| fs::write(cache_path, serialized));
+    }
+}
+
+/// Parse the introspection JSON at `path`. With a `cache_dir`, the processed schema is cached
+/// there and reused for as long as the JSON does not change.
+pub fn parse_schema(
+    path: &Path,
+    cache_dir: Option<&Path>,
+) -> Result<Schema, Vec<PrintableMessage>> {
+    let contents = fs::read(path).map_err(|io_error| {
         let error = PrintableMessage::new_compile_error_from_read_io_error(&io_error, path);
         vec![error]
     })?;
-    let reader = BufReader::new(file);
-    Schema::try_from_reader(reader).map_err(|schema_error| {
+    let cache_path =
+        cache_dir.map(|cache_dir| processed_schema_cache_path(cache_dir, path, &contents));
+    if let Some(schema) = cache_path.as_deref().and_then(read_processed_schema) {
+        return Ok(schema);
+    }
+    let schema = parse_schema_contents(&contents)?;
+    if let Some(cache_path) = cache_path {
+        write_processed_schema(&cache_path, &schema);
+    }
+    Ok(schema)
+}
+
+/// Parse the contents of an introspection JSON file
+pub fn parse_schema_contents(contents: &[u8]) -> Result<Schema, Vec<PrintableMessage>> {
+    Schema::try_from_slice(contents).map_err(|schema_error| {
         let printable_message = match schema_error {
             Error::MissingTypeOfForNonNull | Error::MissingTypeOfForList => {
                 printable_message_error("missing type of information on field")
@@ -0,0 +1,406 @@
+//! Compare two schemas, and what the documents of a tree compile to against each
+use super::field::{Field, FieldType};
+use super::{EnumType, FieldsLookup, Schema, TypeDefinition};
+use crate::cli::PrintableMessage;
+use crate::graphql::{graphql_file_paths, CompileConfig};
+use crate::Severity;
+use colored::Colorize;
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::fmt;
+use std::path::Path;
+
+/// A difference between two schemas. Breaking changes are those that can make a document that
+/// compiled against the old schema fail to compile, or its generated types no longer hold.
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct SchemaChange {
+    /// Schema coordinate of what changed, ie `User.email`, for sorting
+    coordinate: String,
+    description: String,
+    breaking: bool,
+}
+
+impl SchemaChange {
+    fn new(coordinate: String, description: String, breaking: bool) -> Self {
+        SchemaChange {
+            coordinate,
+            description,
+            breaking,
+        }
+    }
+
+    pub fn is_breaking(&self) -> bool {
+        self.breaking
+    }
+
+    /// The change as a single line JSON object, for `--diagnostics-format=json`
+    pub fn to_json(&self) -> serde_json::Value {
+        serde_json::json!({
+            "coordinate": self.coordinate,
+            "description": self.description,
+            "breaking": self.breaking,
+        })
+    }
+}
+
+impl fmt::Display for SchemaChange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let header = if self.breaking {
+            "breaking".red()
+        } else {
+            "change".blue()
+        };
+        write!(f, "{}{} {}", header.bold(), ":".bold(), self.description)
+    }
+}
+
+fn kind_name(definition: &TypeDefinition) -> &'static str {
+    match definition {
+        TypeDefinition::Object(_) => "object",
+        TypeDefinition::Enum(_) => "enum",
+        TypeDefinition::Scalar(_) => "scalar",
+        TypeDefinition::Interface(_) => "interface",
+        TypeDefinition::InputObject(_) => "input object",
+        TypeDefinition::Union(_) => "union",
+    }
+}
+
+/// The type without its `!`s, and the positions of the `!`s in it
+fn non_null_positions(field_type: &FieldType) -> (String, BTreeSet<usize>) {
+    let mut nullable = String::new();
+    let mut positions = BTreeSet::new();
+    for c in field_type.to_string().chars() {
+        if c == '!' {
+            positions.insert(nullable.len());
+        } else {
+            nullable.push(c);
+        }
+    }
+    (nullable, positions)
+}
+
+/// Whether changing a type from `old` to `new` cannot break anything. Output positions may only
+/// become stricter, and input positions may only become looser.
+fn is_safe_type_change(old: &FieldType, new: &FieldType, is_input: bool) -> bool {
+    let (old_nullable, old_positions) = non_null_positions(old);
+    let (new_nullable, new_positions) = non_null_positions(new);
+    old_nullable == new_nullable
+        && if is_input {
+            new_positions.is_subset(&old_positions)
+        } else {
+            old_positions.is_subset(&new_positions)
+        }
+}
+
+fn is_required(field_type: &FieldType) -> bool {
+    field_type.to_string().ends_with('!')
+}
+
+fn sorted_names<'a>(
+    old: impl Iterator<Item = &'a String>,
+    new: impl Iterator<Item = &'a String>,
+) -> BTreeSet<&'a String> {
+    old.chain(new)
+        .filter(|name| !name.starts_with("__"))
+        .collect()
+}
+
+fn diff_type_change(
+    changes: &mut Vec<SchemaChange>,
+    coordinate: String,
+    kind: &str,
+    old: &FieldType,
+    new: &FieldType,
+    is_input: bool,
+) {
+    if old.to_string() != new.to_string() {
+        let description = format!("{kind} `{coordinate}` changed type from `{old}` to `{new}`");
+        let breaking = !is_safe_type_change(old, new, is_input);
+        changes.push(SchemaChange::new(coordinate, description, breaking));
+    }
+}
+
+fn diff_arguments(
+    changes: &mut Vec<SchemaChange>,
+    field_coordinate: &str,
+    old: &Field,
+    new: &Field,
+) {
+    let old_arguments: HashMap<_, _> = old.arguments.iter().map(|a| (&a.name, a)).collect();
+    let new_arguments: HashMap<_, _> = new.arguments.iter().map(|a| (&a.name, a)).collect();
+    for name in sorted_names(old_arguments.keys().copied(), new_arguments.keys().copied()) {
+        let coordinate = format!("{field_coordinate}({name}:)");
+        match (old_arguments.get(name), new_arguments.get(name)) {
+            (Some(_), None) => {
+                let description = format!("argument `{coordinate}` was removed");
+                changes.push(SchemaChange::new(coordinate, description, true));
+            }
+            (None, Some(argument)) => {
+                let breaking = is_required(&argument.type_description);
+                let description = format!(
+                    "argument `{coordinate}` of type `{}` was added",
+                    argument.type_description
+                );
+                changes.push(SchemaChange::new(coordinate, description, breaking));
+            }
+            (Some(old_argument), Some(new_argument)) => diff_type_change(
+                changes,
+                coordinate,
+                "argument",
+                &old_argument.type_description,
+                &new_argument.type_description,
+                true,
+            ),
+            (None, None) => {}
+        }
+    }
+}
+
+fn diff_fields(
+    changes: &mut Vec<SchemaChange>,
+    type_name: &str,
+    old: &FieldsLookup,
+    new: &FieldsLookup,
+    is_input: bool,
+) {
+    let kind = if is_input { "input field" } else { "field" };
+    for name in sorted_names(old.keys(), new.keys()) {
+        let coordinate = format!("{type_name}.{name}");
+        match (old.get(name), new.get(name)) {
+            (Some(_), None) => {
+                let description = format!("{kind} `{coordinate}` was removed");
+                changes.push(SchemaChange::new(coordinate, description, true));
+            }
+            (None, Some(field)) => {
+                let breaking = is_input && is_required(&field.type_description);
+                let description = format!(
+                    "{kind} `{coordinate}` of type `{}` was added",
+                    field.type_description
+                );
+                changes.push(SchemaChange::new(coordinate, description, breaking));
+            }
+            (Some(old_field), Some(new_field)) => {
+                diff_type_change(
+                    changes,
+                    coordinate.clone(),
+                    kind,
+                    &old_field.type_description,
+                    &new_field.type_description,
+                    is_input,
+                );
+                if !old_field.deprecated && new_field.deprecated {
+                    let description = format!("{kind} `{coordinate}` was deprecated");
+                    changes.push(SchemaChange::new(coordinate.clone(), description, false));
+                }
+                diff_arguments(changes, &coordinate, old_field, new_field);
+            }
+            (None, None) => {}
+        }
+    }
+}
+
+/// Differences of a list of names of a type, such as enum values or the possible types of a
+/// union. Removing one is breaking.
+fn diff_members(
+    changes: &mut Vec<SchemaChange>,
+    type_name: &str,
+    kind: &str,
+    old: &[String],
+    new: &[String],
+) {
+    for name in sorted_names(old.iter(), new.iter()) {
+        let (coordinate, description, breaking) = match (old.contains(name), new.contains(name)) {
+            (true, false) => (
+                format!("{type_name}.{name}"),
+                format!("{kind} `{name}` was removed from `{type_name}`"),
+                true,
+            ),
+            (false, true) => (
+                format!("{type_name}.{name}"),
+                format!("{kind} `{name}` was added to `{type_name}`"),
+                false,
+            ),
+            _ => continue,
+        };
+        changes.push(SchemaChange::new(coordinate, description, breaking));
+    }
+}
+
+fn diff_root_types(changes: &mut Vec<SchemaChange>, old: &Schema, new: &Schema) {
+    let root_types = [
+        ("query", old.query_type_name(), new.query_type_name()),
+        (
+            "mutation",
+            old.mutation_type_name(),
+            new.mutation_type_name(),
+        ),
+        (
+            "subscription",
+            old.subscription_type_name(),
+            new.subscription_type_name(),
+        ),
+    ];
+    for (operation, old_name, new_name) in root_types {
+        let description = match (old_name, new_name) {
+            (Some(old_name), Some(new_name)) if old_name != new_name => {
+                format!("{operation} root type changed from `{old_name}` to `{new_name}`")
+            }
+            (Some(_), None) => format!("{operation} root type was removed"),
+            (None, Some(new_name)) => format!("{operation} root type `{new_name}` was added"),
+            _ => continue,
+        };
+        let breaking = old_name.is_some();
+        changes.push(SchemaChange::new(String::new(), description, breaking));
+    }
+}
+
+/// Every difference from `old` to `new`, sorted by what changed
+pub fn diff_schemas(old: &Schema, new: &Schema) -> Vec<SchemaChange> {
+    let mut changes = Vec::new();
+    diff_root_types(&mut changes, old, new);
+    for name in sorted_names(old.type_names(), new.type_names()) {
+        let (old_type, new_type) = match (old.get_type_for_name(name), new.get_type_for_name(name))
+        {
+            (Some(old_type), Some(new_type)) => (old_type, new_type),
+            (Some(old_type), None) => {
+                let kind = kind_name(&old_type.definition);
+                let description = format!("{kind} `{name}` was removed");
+                changes.push(SchemaChange::new(name.clone(), description, true));
+                continue;
+            }
+            (None, Some(new_type)) => {
+                let kind = kind_name(&new_type.definition);
+                let description = format!("{kind} `{name}` was added");
+                changes.push(SchemaChange::new(name.clone(), description, false));
+                continue;
+            }
+            (None, None) => continue,
+        };
+        match (&old_type.definition, &new_type.definition) {
+            (TypeDefinition::Object(old_object), TypeDefinition::Object(new_object)) => {
+                diff_fields(
+                    &mut changes,
+                    name,
+                    &old_object.fields,
+                    &new_object.fields,
+                    false,
+                );
+            }
+            (
+                TypeDefinition::Interface(old_interface),
+                TypeDefinition::Interface(new_interface),
+            ) => {
+                diff_fields(
+                    &mut changes,
+                    name,
+                    &old_interface.fields,
+                    &new_interface.fields,
+                    false,
+                );
+                diff_members(
+                    &mut changes,
+                    name,
+                    "implementation",
+                    &old_interface.possible_types,
+                    &new_interface.possible_types,
+                );
+            }
+            (TypeDefinition::InputObject(old_input), TypeDefinition::InputObject(new_input)) => {
+                diff_fields(
+                    &mut changes,
+                    name,
+                    &old_input.fields,
+                    &new_input.fields,
+                    true,
+                );
+            }
+            (TypeDefinition::Union(old_union), TypeDefinition::Union(new_union)) => {
+                diff_members(
+                    &mut changes,
+                    name,
+                    "member",
+                    &old_union.possible_types,
+                    &new_union.possible_types,
+                );
+            }
+            (TypeDefinition::Enum(old_enum), TypeDefinition::Enum(new_enum)) => {
+                let value_names = |enum_type: &EnumType| -> Vec<String> {
+                    enum_type
+                        .values
+                        .iter()
+                        .map(|value| value.name.clone())
+                        .collect()
+                };
+                diff_members(
+                    &mut changes,
+                    name,
+                    "enum value",
+                    &value_names(old_enum),
+                    &value_names(new_enum),
+                );
+            }
+            (TypeDefinition::Scalar(_), TypeDefinition::Scalar(_)) => {}
+            (old_definition, new_definition) => {
+                let description = format!(
+                    "`{name}` changed from {} to {}",
+                    kind_name(old_definition),
+                    kind_name(new_definition)
+                );
+                changes.push(SchemaChange::new(name.clone(), description, true));
+            }
+        }
+    }
+    changes.sort();
+    changes
+}
+
+/// Messages for the documents under `documents_dir` that compile against `old` but not against
+/// `new`, and for those whose generated types differ between the two
+pub fn document_messages(
+    config: &CompileConfig,
+    old: &Schema,
+    new: &Schema,
+    documents_dir: &Path,
+) -> Vec<PrintableMessage> {
+    let mut paths = Vec::new();
+    graphql_file_paths(documents_dir, &mut paths);
+    paths.sort();
+    let mut messages = Vec::new();
+    let mut old_global_types_used = HashSet::new();
+    let mut new_global_types_used = HashSet::new();
+    for path in paths {
+        let old_compile = crate::compile_file(config, old, &path);
+        // Already broken, which is not the new schema's doing
+        if old_compile.has_errors() {
+            continue;
+        }
+        let new_compile = crate::compile_file(config, new, &path);
+        if new_compile.has_errors() {
+            messages.extend(
+                new_compile
+                    .diagnostics
+                    .into_iter()
+                    .filter(|diagnostic| diagnostic.severity() == Severity::Error)
+                    .map(|diagnostic| diagnostic.0),
+            );
+        } else if old_compile.output != new_compile.output {
+            let mut message = PrintableMessage::new_simple_compile_warning(
+                "generated types change with the new schema",
+            );
+            message.with_source_information(&path, None);
+            messages.push(message);
+        }
+        old_global_types_used.extend(old_compile.global_types_used);
+        new_global_types_used.extend(new_compile.global_types_used);
+    }
+    let old_globals = crate::compile_global_types(config, old, &old_global_types_used);
+    let new_globals = crate::compile_global_types(config, new, &new_global_types_used);
+    match (old_globals, new_globals) {
+        (Ok(old_globals), Ok(new_globals)) if old_globals == new_globals => {}
+        (_, Err(diagnostic)) => messages.push(diagnostic.0),
+        _ => messages.push(PrintableMessage::new_simple_compile_warning(&format!(
+            "generated types of the `{}` module change with the new schema",
+            config.global_types_module_name
+        ))),
+    }
+    messages
+}
@@ -0,0 +1,208 @@
+//! Download introspection JSON from a GraphQL endpoint
+use super::parse_schema_contents;
+use crate::cli::{PrintableMessage, RuntimeConfig};
+use serde::Deserialize;
+use std::io::Read;
+use std::path::Path;
+use std::time::Duration;
+
+const TOKEN_ENV_VAR: &str = "QLC_SCHEMA_TOKEN";
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// The introspection query of the GraphQL reference implementation, with its default options
+const INTROSPECTION_QUERY: &str = "
+  query IntrospectionQuery {
+    __schema {
+      queryType { name }
+      mutationType { name }
+      subscriptionType { name }
+      types {
+        ...FullType
+      }
+      directives {
+        name
+        description
+        locations
+        args {
+          ...InputValue
+        }
+      }
+    }
+  }
+
+  fragment FullType on __Type {
+    kind
+    name
+    description
+    fields(includeDeprecated: true) {
+      name
+      description
+      args {
+        ...InputValue
+      }
+      type {
+        ...TypeRef
+      }
+      isDeprecated
+      deprecationReason
+    }
+    inputFields {
+      ...InputValue
+    }
+    interfaces {
+      ...TypeRef
+    }
+    enumValues(includeDeprecated: true) {
+      name
+      description
+      isDeprecated
+      deprecationReason
+    }
+    possibleTypes {
+      ...TypeRef
+    }
+  }
+
+  fragment InputValue on __InputValue {
+    name
+    description
+    type { ...TypeRef }
+    defaultValue
+  }
+
+  fragment TypeRef on __Type {
+    kind
+    name
+    ofType {
+      kind
+      name
+      ofType {
+        kind
+        name
+        ofType {
+          kind
+          name
+          ofType {
+            kind
+            name
+            ofType {
+              kind
+              name
+              ofType {
+                kind
+                name
+                ofType {
+                  kind
+                  name
+                }
+              }
+            }
+          }
+        }
+      }
+    }
+  }
+";
+
+#[derive(Deserialize)]
+struct ErrorResponse {
+    errors: Vec<ResponseError>,
+}
+
+#[derive(Deserialize)]
+struct ResponseError {
+    message: String,
+}
+
+fn fetch_error(url: &str, reason: &str) -> Vec<PrintableMessage> {
+    vec![PrintableMessage::new_simple_program_error(&format!(
+        "could not fetch schema from `{url}`: {reason}"
+    ))]
+}
+
+/// POST the introspection query to `url`, returning the response once it is known to be a schema
+/// qlc can use
+pub fn fetch_schema(
+    url: &str,
+    headers: &[(String, String)],
+) -> Result<Vec<u8>, Vec<PrintableMessage>> {
+    let agent = ureq::AgentBuilder::new().timeout(REQUEST_TIMEOUT).build();
+    let mut request = agent
+        .post(url)
+        .set("Content-Type", "application/json")
+        .set("Accept", "application/json");
+    for (name, value) in headers {
+        request = request.set(name, value);
+    }
+    let body = serde_json::json!({
+        "operationName": "IntrospectionQuery",
+        "query": INTROSPECTION_QUERY,
+    });
+    let (error_status, response) = match request.send_string(&body.to_string()) {
+        Ok(response) => (None, response),
+        // Servers may answer with an error status and still explain why in GraphQL errors
+        Err(ureq::Error::Status(status, response)) => (Some(status), response),
+        Err(error) => return Err(fetch_error(url, &error.to_string())),
+    };
+    let mut contents = Vec::new();
+    response
+        .into_reader()
+        .read_to_end(&mut contents)
+        .map_err(|io_error| fetch_error(url, &io_error.to_string()))?;
+
+    if let Ok(error_response) = serde_json::from_slice::<ErrorResponse>(&contents) {
+        if !error_response.errors.is_empty() {
+            return Err(error_response
+                .errors
+                .into_iter()
+                .map(|error| {
+                    PrintableMessage::new_simple_program_error(&format!(
+                        "introspection of `{url}` failed: {}",
+                        error.message
+                    ))
+                })
+                .collect());
+        }
+    }
+    if let Some(status) = error_status {
+        return Err(fetch_error(url, &format!("status code {status}")));
+    }
+    parse_schema_contents(&contents)?;
+    Ok(contents)
+}
+
+/// Run `qlc schema fetch`, writing the schema of `url` (or the configured URL) to `output` (or
+/// the configured schema path). Headers from the command line win over configured ones.
+pub fn fetch_schema_to_file(
+    config: &RuntimeConfig,
+    url: Option<&str>,
+    output: Option<&Path>,
+    cli_headers: &[(String, String)],
+) -> Result<(), Vec<PrintableMessage>> {
+    let url = url.or_else(|| config.schema_url()).ok_or_else(|| {
+        vec![PrintableMessage::new_simple_program_error(
+            "no schema URL, pass one or set `schemaUrl` in the config file",
+        )]
+    })?;
+    let mut headers = Vec::new();
+    if let Some(token) = std::env::var(TOKEN_ENV_VAR)
+        .ok()
+        .filter(|token| !token.is_empty())
+    {
+        headers.push((String::from("Authorization"), format!("Bearer {token}")));
+    }
+    for (name, value) in config.schema_headers().iter().chain(cli_headers) {
+        headers.retain(|(existing_name, _): &(String, String)| {
+            !existing_name.eq_ignore_ascii_case(name)
+        });
+        headers.push((name.clone(), value.clone()));
+    }
+
+    let contents = fetch_schema(url, &headers)?;
+    let output = output.map_or_else(|| config.schema_file_path(), Path::to_path_buf);
+    std::fs::write(&output, contents).map_err(|io_error| {
+        vec![PrintableMessage::new_compile_error_from_write_io_error(
+            &io_error, &output,
+        )]
+    })
+}
@@ -1,7 +1,9 @@
 use super::{json, Documentation, Error};
+use serde::{Deserialize, Serialize};
 use std::convert::{TryFrom, TryInto};
+use std::fmt;
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum ScalarType {
     Custom(String),
     Boolean,
@@ -24,7 +26,7 @@ impl From<&str> for ScalarType {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Serialize, Deserialize)]
 pub enum FieldTypeDefinition {
     Object,
     Interface,
@@ -44,7 +46,7 @@ impl FieldTypeDefinition {
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub enum FieldTypeModifier {
     /// No modifier or "flat"
     None,
@@ -66,7 +68,7 @@ impl FieldTypeModifier {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct ConcreteFieldType {
     pub name: String,
     pub modifier: FieldTypeModifier,
@@ -127,7 +129,7 @@ impl ModifierBuilder {
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct FieldTypeModifiers {
     last: FieldTypeModifier,
     rest: Vec<FieldTypeModifier>,
@@ -150,7 +152,7 @@ impl FieldTypeModifiers {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct FieldType {
     concrete: ConcreteFieldType,
     higher_order_modifiers: Vec<FieldTypeModifier>,
@@ -182,6 +184,32 @@ impl FieldType {
     }
 }
 
+impl fmt::Display for FieldType {
+    /// Formats the type the way it is written in GraphQL, ie `[ID!]!`
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // Higher order modifiers are ordered from the innermost list outwards
+        for _ in &self.higher_order_modifiers {
+            write!(f, "[")?;
+        }
+        let name = &self.concrete.name;
+        match self.concrete.modifier {
+            FieldTypeModifier::None => write!(f, "{name}!"),
+            FieldTypeModifier::Nullable => write!(f, "{name}"),
+            FieldTypeModifier::List => write!(f, "[{name}!]!"),
+            FieldTypeModifier::NullableList => write!(f, "[{name}!]"),
+            FieldTypeModifier::ListOfNullable => write!(f, "[{name}]!"),
+            FieldTypeModifier::NullableListOfNullable => write!(f, "[{name}]"),
+        }?;
+        for modifier in &self.higher_order_modifiers {
+            match modifier {
+                FieldTypeModifier::List | FieldTypeModifier::ListOfNullable => write!(f, "]!"),
+                _ => write!(f, "]"),
+            }?;
+        }
+        Ok(())
+    }
+}
+
 impl TryFrom<json::FieldType> for FieldType {
     type Error = Error;
     fn try_from(json: json::FieldType) -> Result<Self, Self::Error> {
@@ -234,12 +262,25 @@ impl TryFrom<json::FieldType> for FieldType {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Serialize, Deserialize)]
+pub struct FieldArgument {
+    pub name: String,
+    pub documentation: Documentation,
+    pub type_description: FieldType,
+    /// Written as a GraphQL value, ie `10` or `"name"`
+    pub default_value: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
 pub struct Field {
     pub name: String,
     pub documentation: Documentation,
     pub type_description: FieldType,
     pub deprecated: bool,
+    pub deprecation_reason: Option<String>,
+    pub arguments: Vec<FieldArgument>,
+    /// Default of an input field, written as a GraphQL value
+    pub default_value: Option<String>,
 }
 
 impl Field {
@@ -249,10 +290,35 @@ impl Field {
             documentation: None,
             type_description: FieldType::new_type_name(),
             deprecated: false,
+            deprecation_reason: None,
+            arguments: Vec::new(),
+            default_value: None,
         }
     }
 }
 
+pub(super) fn documentation_from_description(description: Option<String>) -> Documentation {
+    description.map(|docs| {
+        docs.lines()
+            .map(|line| line.trim())
+            .filter(|line| !line.is_empty())
+            .collect::<Vec<_>>()
+            .join("\n")
+    })
+}
+
+impl TryFrom<json::Field> for FieldArgument {
+    type Error = Error;
+    fn try_from(json: json::Field) -> Result<Self, Error> {
+        Ok(FieldArgument {
+            name: json.name,
+            documentation: documentation_from_description(json.description),
+            type_description: json.type_information.try_into()?,
+            default_value: json.default_value,
+        })
+    }
+}
+
 impl TryFrom<json::Field> for Field {
     type Error = Error;
     fn try_from(json: json::Field) -> Result<Self, Error> {
@@ -261,18 +327,22 @@ impl TryFrom<json::Field> for Field {
             name,
             description,
             deprecated,
+            deprecation_reason,
+            args,
+            default_value,
         } = json;
         Ok(Field {
             name,
-            documentation: description.map(|docs| {
-                docs.lines()
-                    .map(|line| line.trim())
-                    .filter(|line| !line.is_empty())
-                    .collect::<Vec<_>>()
-                    .join("\n")
-            }),
+            documentation: documentation_from_description(description),
             deprecated: deprecated.unwrap_or(false),
+            deprecation_reason,
             type_description: type_information.try_into()?,
+            arguments: args
+                .unwrap_or_default()
+                .into_iter()
+                .map(FieldArgument::try_from)
+                .collect::<Result<_, _>>()?,
+            default_value,
         })
     }
 }
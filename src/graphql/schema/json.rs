@@ -1,6 +1,5 @@
 //! Deserialzing and handling introspection JSON
 use serde::Deserialize;
-use std::io::Read;
 
 #[derive(Deserialize, Debug)]
 pub struct FieldType {
@@ -18,6 +17,13 @@ pub struct Field {
     pub type_information: FieldType,
     #[serde(rename(deserialize = "isDeprecated"))]
     pub deprecated: Option<bool>,
+    #[serde(rename(deserialize = "deprecationReason"))]
+    pub deprecation_reason: Option<String>,
+    /// Arguments of an output field, absent for input fields and arguments themselves
+    pub args: Option<Vec<Field>>,
+    /// Default of an input field or argument, written as a GraphQL value
+    #[serde(rename(deserialize = "defaultValue"))]
+    pub default_value: Option<String>,
 }
 
 #[derive(Deserialize, Debug)]
@@ -25,6 +31,29 @@ pub struct ComplexObjectDescription {
     pub name: String,
 }
 
+#[derive(Deserialize, Debug)]
+pub struct EnumValue {
+    pub name: String,
+    pub description: Option<String>,
+    #[serde(rename(deserialize = "isDeprecated"))]
+    pub deprecated: Option<bool>,
+    #[serde(rename(deserialize = "deprecationReason"))]
+    pub deprecation_reason: Option<String>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct Directive {
+    pub name: String,
+    pub description: Option<String>,
+    /// Directives are not needed to compile, so ones that leave these out are not worth failing on
+    #[serde(default)]
+    pub locations: Vec<String>,
+    #[serde(default)]
+    pub args: Vec<Field>,
+    #[serde(rename(deserialize = "isRepeatable"))]
+    pub repeatable: Option<bool>,
+}
+
 #[derive(Deserialize, Debug)]
 pub struct Type {
     pub kind: String,
@@ -36,17 +65,26 @@ pub struct Type {
     pub input_fields: Option<Vec<Field>>,
     pub fields: Option<Vec<Field>>,
     #[serde(rename(deserialize = "enumValues"))]
-    pub enum_values: Option<Vec<ComplexObjectDescription>>,
+    pub enum_values: Option<Vec<EnumValue>>,
+    /// Interfaces implemented by an object or interface
+    pub interfaces: Option<Vec<ComplexObjectDescription>>,
 }
 
 #[derive(Deserialize, Debug)]
 pub struct Schema {
     pub types: Vec<Type>,
+    #[serde(rename(deserialize = "queryType"))]
+    pub query_type: Option<ComplexObjectDescription>,
+    #[serde(rename(deserialize = "mutationType"))]
+    pub mutation_type: Option<ComplexObjectDescription>,
+    #[serde(rename(deserialize = "subscriptionType"))]
+    pub subscription_type: Option<ComplexObjectDescription>,
+    pub directives: Option<Vec<Directive>>,
 }
 
 impl Schema {
-    pub fn try_from_reader(reader: impl Read) -> Result<Self, serde_json::Error> {
-        let parsed: RawSchema = serde_json::from_reader(reader)?;
+    pub fn try_from_slice(contents: &[u8]) -> Result<Self, serde_json::Error> {
+        let parsed: RawSchema = serde_json::from_slice(contents)?;
         Ok(parsed.data.schema)
     }
 }
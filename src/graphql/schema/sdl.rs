@@ -0,0 +1,245 @@
+//! Print the schema as GraphQL SDL
+use super::field::{Field, FieldArgument};
+use super::{Directive, Documentation, EnumValue, Schema, Type, TypeDefinition};
+use std::fmt::Write;
+
+const BUILT_IN_SCALARS: [&str; 5] = ["String", "Int", "Float", "Boolean", "ID"];
+const BUILT_IN_DIRECTIVES: [&str; 5] = ["include", "skip", "deprecated", "specifiedBy", "oneOf"];
+const DEFAULT_DEPRECATION_REASON: &str = "No longer supported";
+
+/// Render `schema` as SDL. Types and directives are sorted by name, as are fields, interfaces and
+/// union members. Arguments and enum values keep the order of the schema.
+pub fn print_schema(schema: &Schema) -> String {
+    let mut definitions = Vec::new();
+    if let Some(schema_definition) = schema_definition(schema) {
+        definitions.push(schema_definition);
+    }
+
+    let mut directives: Vec<_> = schema
+        .directives()
+        .iter()
+        .filter(|directive| !BUILT_IN_DIRECTIVES.contains(&directive.name.as_str()))
+        .collect();
+    directives.sort_unstable_by(|a, b| a.name.cmp(&b.name));
+    definitions.extend(directives.into_iter().map(print_directive));
+
+    let mut type_names: Vec<_> = schema
+        .type_names()
+        .filter(|name| !name.starts_with("__") && !BUILT_IN_SCALARS.contains(&name.as_str()))
+        .collect();
+    type_names.sort_unstable();
+    definitions.extend(type_names.into_iter().map(|name| {
+        let schema_type = schema.get_type_for_name(name).unwrap();
+        print_type(name, schema_type)
+    }));
+
+    let mut sdl = definitions.join("\n\n");
+    sdl.push('\n');
+    sdl
+}
+
+/// The `schema` definition is only needed when the root types do not have their usual names
+fn schema_definition(schema: &Schema) -> Option<String> {
+    let roots = [
+        ("query", schema.query_type_name(), "Query"),
+        ("mutation", schema.mutation_type_name(), "Mutation"),
+        (
+            "subscription",
+            schema.subscription_type_name(),
+            "Subscription",
+        ),
+    ];
+    if roots
+        .iter()
+        .all(|(_, name, usual_name)| name.is_none() || *name == Some(*usual_name))
+    {
+        return None;
+    }
+    let mut definition = String::from("schema {\n");
+    for (operation, name, _) in roots {
+        if let Some(name) = name {
+            let _ = writeln!(definition, "  {operation}: {name}");
+        }
+    }
+    definition.push('}');
+    Some(definition)
+}
+
+fn print_type(name: &str, schema_type: &Type) -> String {
+    let mut output = description(&schema_type.documentation, 0);
+    match &schema_type.definition {
+        TypeDefinition::Scalar(_) => {
+            let _ = write!(output, "scalar {name}");
+        }
+        TypeDefinition::Object(object_type) => {
+            let _ = write!(
+                output,
+                "type {name}{} {}",
+                implements(&object_type.interfaces),
+                print_fields(&object_type.fields)
+            );
+        }
+        TypeDefinition::Interface(interface_type) => {
+            let _ = write!(
+                output,
+                "interface {name}{} {}",
+                implements(&interface_type.interfaces),
+                print_fields(&interface_type.fields)
+            );
+        }
+        TypeDefinition::Union(union_type) => {
+            let mut members: Vec<_> = union_type
+                .possible_types
+                .iter()
+                .map(String::as_str)
+                .collect();
+            members.sort_unstable();
+            let _ = write!(output, "union {name} = {}", members.join(" | "));
+        }
+        TypeDefinition::Enum(enum_type) => {
+            let values: Vec<_> = enum_type.values.iter().map(print_enum_value).collect();
+            let _ = write!(output, "enum {name} {{\n{}\n}}", values.join("\n"));
+        }
+        TypeDefinition::InputObject(input_object_type) => {
+            let _ = write!(
+                output,
+                "input {name} {}",
+                print_fields(&input_object_type.fields)
+            );
+        }
+    }
+    output
+}
+
+fn implements(interfaces: &[String]) -> String {
+    if interfaces.is_empty() {
+        return String::new();
+    }
+    let mut interfaces: Vec<_> = interfaces.iter().map(String::as_str).collect();
+    interfaces.sort_unstable();
+    format!(" implements {}", interfaces.join(" & "))
+}
+
+fn print_fields(fields: &super::FieldsLookup) -> String {
+    let mut fields: Vec<_> = fields
+        .values()
+        .filter(|field| !field.name.starts_with("__"))
+        .collect();
+    fields.sort_unstable_by(|a, b| a.name.cmp(&b.name));
+    let printed: Vec<_> = fields.into_iter().map(print_field).collect();
+    format!("{{\n{}\n}}", printed.join("\n"))
+}
+
+fn print_field(field: &Field) -> String {
+    let mut output = description(&field.documentation, 2);
+    let _ = write!(
+        output,
+        "  {}{}: {}{}{}",
+        field.name,
+        print_arguments(&field.arguments, 2),
+        field.type_description,
+        default_value(field.default_value.as_deref()),
+        deprecation(field.deprecated, field.deprecation_reason.as_deref())
+    );
+    output
+}
+
+fn print_enum_value(value: &EnumValue) -> String {
+    let mut output = description(&value.documentation, 2);
+    let _ = write!(
+        output,
+        "  {}{}",
+        value.name,
+        deprecation(value.deprecated, value.deprecation_reason.as_deref())
+    );
+    output
+}
+
+fn print_directive(directive: &Directive) -> String {
+    let mut output = description(&directive.documentation, 0);
+    let _ = write!(
+        output,
+        "directive @{}{}{} on {}",
+        directive.name,
+        print_arguments(&directive.arguments, 0),
+        if directive.repeatable {
+            " repeatable"
+        } else {
+            ""
+        },
+        directive.locations.join(" | ")
+    );
+    output
+}
+
+/// Arguments go on one line, unless any of them are described
+fn print_arguments(arguments: &[FieldArgument], indent: usize) -> String {
+    if arguments.is_empty() {
+        return String::new();
+    }
+    let print_argument = |argument: &FieldArgument| {
+        format!(
+            "{}: {}{}",
+            argument.name,
+            argument.type_description,
+            default_value(argument.default_value.as_deref())
+        )
+    };
+    if arguments
+        .iter()
+        .all(|argument| argument.documentation.is_none())
+    {
+        let printed: Vec<_> = arguments.iter().map(print_argument).collect();
+        return format!("({})", printed.join(", "));
+    }
+    let argument_indent = indent + 2;
+    let printed: Vec<_> = arguments
+        .iter()
+        .map(|argument| {
+            format!(
+                "{}{:argument_indent$}{}",
+                description(&argument.documentation, argument_indent),
+                "",
+                print_argument(argument)
+            )
+        })
+        .collect();
+    format!("(\n{}\n{:indent$})", printed.join("\n"), "")
+}
+
+fn default_value(value: Option<&str>) -> String {
+    value.map(|value| format!(" = {value}")).unwrap_or_default()
+}
+
+fn deprecation(deprecated: bool, reason: Option<&str>) -> String {
+    match reason {
+        _ if !deprecated => String::new(),
+        Some(reason) if reason != DEFAULT_DEPRECATION_REASON => {
+            // JSON string escapes are all valid in GraphQL strings
+            let reason = serde_json::Value::from(reason);
+            format!(" @deprecated(reason: {reason})")
+        }
+        _ => String::from(" @deprecated"),
+    }
+}
+
+/// Block string of the description, on its own line(s) before what it describes
+fn description(documentation: &Documentation, indent: usize) -> String {
+    let Some(documentation) = documentation.as_deref().filter(|docs| !docs.is_empty()) else {
+        return String::new();
+    };
+    let escaped = documentation.replace("\"\"\"", "\\\"\"\"");
+    if !escaped.contains('\n') && !escaped.ends_with('"') && !escaped.ends_with('\\') {
+        return format!("{:indent$}\"\"\"{escaped}\"\"\"\n", "");
+    }
+    let mut output = format!("{:indent$}\"\"\"\n", "");
+    for line in escaped.lines() {
+        if line.is_empty() {
+            output.push('\n');
+        } else {
+            let _ = writeln!(output, "{:indent$}{line}", "");
+        }
+    }
+    let _ = writeln!(output, "{:indent$}\"\"\"", "");
+    output
+}
@@ -0,0 +1,74 @@
+//! The type policies module, a skeleton of Apollo's `typePolicies` with the key field of every
+//! object type
+use crate::cli::PrintableMessage;
+use crate::graphql::schema::{FieldsLookup, Schema, TypeDefinition};
+use crate::typescript::HEADER;
+use std::path::Path;
+
+pub const DEFAULT_TYPE_POLICIES_FILE_NAME: &str = "type-policies.ts";
+pub const DEFAULT_KEY_FIELD: &str = "id";
+
+/// The first of `key_fields` that a type has, which identifies its objects in the cache
+pub fn key_field<'a>(fields: &FieldsLookup, key_fields: &'a [String]) -> Option<&'a str> {
+    key_fields
+        .iter()
+        .find(|key_field| fields.contains_key(*key_field))
+        .map(String::as_str)
+}
+
+/// Write the type policies module to `output_path`. Root operation types and objects without a key
+/// field are typed but get no policy, leaving Apollo's default normalization to them.
+pub fn compile_type_policies_file(
+    output_path: &Path,
+    schema: &Schema,
+    key_fields: &[String],
+) -> Result<(), PrintableMessage> {
+    let mut object_types: Vec<_> = schema
+        .type_names()
+        .filter(|name| !name.starts_with("__"))
+        .filter_map(|name| match &schema.get_type_for_name(name)?.definition {
+            TypeDefinition::Object(object_type) => {
+                Some((name.as_str(), key_field(&object_type.fields, key_fields)))
+            }
+            _ => None,
+        })
+        .collect();
+    object_types.sort_unstable();
+
+    let root_type_names = [
+        schema.query_type_name(),
+        schema.mutation_type_name(),
+        schema.subscription_type_name(),
+    ];
+    let mut key_field_types = String::new();
+    let mut policies = String::new();
+    for (name, key_field) in object_types {
+        let key_field_type =
+            key_field.map_or_else(|| String::from("never"), |f| format!("\"{f}\""));
+        key_field_types.push_str(&format!("  {name}: {key_field_type};\n"));
+        let Some(key_field) = key_field.filter(|_| !root_type_names.contains(&Some(name))) else {
+            continue;
+        };
+        policies.push_str(&format!("  {name}: {{ keyFields: [\"{key_field}\"] }},\n"));
+    }
+
+    let contents = format!(
+        "{HEADER}import type {{ TypePolicies }} from \"@apollo/client\";
+
+/**
+ * Key field of every object type, `never` for those without one
+ */
+export type KeyFields = {{
+{key_field_types}}};
+
+export type ObjectTypename = keyof KeyFields;
+
+const typePolicies: TypePolicies = {{
+{policies}}};
+
+export default typePolicies;"
+    );
+    std::fs::write(output_path, contents).map_err(|io_error| {
+        PrintableMessage::new_compile_error_from_write_io_error(&io_error, output_path)
+    })
+}
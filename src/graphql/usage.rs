@@ -0,0 +1,147 @@
+//! Which parts of the schema the documents use, for `--usage-report`
+use super::ParsedTextType;
+use crate::cli::PrintableMessage;
+use crate::graphql::schema::{Schema, TypeDefinition};
+use graphql_parser::query::Value;
+use graphql_parser::Pos;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum UsageKind {
+    Field,
+    EnumValue,
+    InputField,
+}
+
+/// A reference to part of the schema at a position of a document
+#[derive(Debug)]
+pub struct SchemaUsage {
+    /// Schema coordinate of what is used, ie `User.email` or `Role.ADMIN`
+    pub coordinate: String,
+    pub kind: UsageKind,
+    pub deprecated: bool,
+    pub deprecation_reason: Option<String>,
+    pub position: Pos,
+}
+
+/// Usages of enum values and input fields written in the argument `value` of type `type_name`.
+/// Values are not validated against the schema, so anything that does not match is skipped.
+pub fn value_usages(
+    schema: &Schema,
+    type_name: &str,
+    value: &Value<'_, ParsedTextType>,
+    position: Pos,
+    usages: &mut Vec<SchemaUsage>,
+) {
+    let Some(schema_type) = schema.get_type_for_name(type_name) else {
+        return;
+    };
+    match (value, &schema_type.definition) {
+        (Value::List(items), _) => {
+            for item in items {
+                value_usages(schema, type_name, item, position, usages);
+            }
+        }
+        (Value::Enum(value_name), TypeDefinition::Enum(enum_type)) => {
+            if let Some(enum_value) = enum_type.values.iter().find(|v| &v.name == value_name) {
+                usages.push(SchemaUsage {
+                    coordinate: format!("{type_name}.{value_name}"),
+                    kind: UsageKind::EnumValue,
+                    deprecated: enum_value.deprecated,
+                    deprecation_reason: enum_value.deprecation_reason.clone(),
+                    position,
+                });
+            }
+        }
+        (Value::Object(fields), TypeDefinition::InputObject(input_object_type)) => {
+            for (field_name, field_value) in fields {
+                let Some(field) = input_object_type.fields.get(field_name) else {
+                    continue;
+                };
+                usages.push(SchemaUsage {
+                    coordinate: format!("{type_name}.{field_name}"),
+                    kind: UsageKind::InputField,
+                    deprecated: field.deprecated,
+                    deprecation_reason: field.deprecation_reason.clone(),
+                    position,
+                });
+                let field_type_name = &field.type_description.reveal_concrete().name;
+                value_usages(schema, field_type_name, field_value, position, usages);
+            }
+        }
+        _ => {}
+    }
+}
+
+#[derive(Serialize, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "camelCase")]
+struct Reference {
+    file_path: String,
+    line: usize,
+    column: usize,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct UsageEntry {
+    coordinate: String,
+    kind: UsageKind,
+    count: usize,
+    deprecated: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    deprecation_reason: Option<String>,
+    references: Vec<Reference>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct UsageReport {
+    /// Coordinates of everything deprecated that is still used, so they stand out
+    deprecated_in_use: Vec<String>,
+    usages: Vec<UsageEntry>,
+}
+
+/// Write the JSON report of `usages`, made in the documents at their paths, to `report_path`
+pub fn write_usage_report(
+    report_path: &Path,
+    usages: Vec<(PathBuf, SchemaUsage)>,
+) -> Result<(), PrintableMessage> {
+    let mut entries: BTreeMap<String, UsageEntry> = BTreeMap::new();
+    for (path, usage) in usages {
+        let entry = entries
+            .entry(usage.coordinate.clone())
+            .or_insert_with(|| UsageEntry {
+                coordinate: usage.coordinate,
+                kind: usage.kind,
+                count: 0,
+                deprecated: usage.deprecated,
+                deprecation_reason: usage.deprecation_reason,
+                references: Vec::new(),
+            });
+        entry.count += 1;
+        entry.references.push(Reference {
+            file_path: path.display().to_string(),
+            line: usage.position.line,
+            column: usage.position.column,
+        });
+    }
+    let mut usages: Vec<_> = entries.into_values().collect();
+    for entry in &mut usages {
+        entry.references.sort();
+    }
+    let report = UsageReport {
+        deprecated_in_use: usages
+            .iter()
+            .filter(|entry| entry.deprecated)
+            .map(|entry| entry.coordinate.clone())
+            .collect(),
+        usages,
+    };
+    let json = serde_json::to_string_pretty(&report).expect("usage report should serialize");
+    std::fs::write(report_path, json).map_err(|io_error| {
+        PrintableMessage::new_compile_error_from_write_io_error(&io_error, report_path)
+    })
+}
@@ -0,0 +1,1047 @@
+//! Spec validation rules for documents that the IR builder does not already enforce
+use super::ir::ImportedFragments;
+use super::ParsedTextType;
+use crate::cli::{similar_help_suggestions, PrintableMessage};
+use crate::graphql::schema;
+use crate::graphql::schema::field as schema_field;
+use graphql_parser::query as parsed_query;
+use graphql_parser::Pos;
+use std::collections::HashSet;
+use std::path::Path;
+use std::sync::Arc;
+
+type SelectionSet<'a> = parsed_query::SelectionSet<'a, ParsedTextType>;
+type Arguments<'a> = [(String, parsed_query::Value<'a, ParsedTextType>)];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DirectiveLocation {
+    Query,
+    Mutation,
+    Subscription,
+    Field,
+    FragmentDefinition,
+    FragmentSpread,
+    InlineFragment,
+}
+
+impl DirectiveLocation {
+    fn description(&self) -> &'static str {
+        match self {
+            DirectiveLocation::Query => "query",
+            DirectiveLocation::Mutation => "mutation",
+            DirectiveLocation::Subscription => "subscription",
+            DirectiveLocation::Field => "field",
+            DirectiveLocation::FragmentDefinition => "fragment definition",
+            DirectiveLocation::FragmentSpread => "fragment spread",
+            DirectiveLocation::InlineFragment => "inline fragment",
+        }
+    }
+
+    /// The name of the location in the `locations` of introspected directives
+    fn introspection_name(&self) -> &'static str {
+        match self {
+            DirectiveLocation::Query => "QUERY",
+            DirectiveLocation::Mutation => "MUTATION",
+            DirectiveLocation::Subscription => "SUBSCRIPTION",
+            DirectiveLocation::Field => "FIELD",
+            DirectiveLocation::FragmentDefinition => "FRAGMENT_DEFINITION",
+            DirectiveLocation::FragmentSpread => "FRAGMENT_SPREAD",
+            DirectiveLocation::InlineFragment => "INLINE_FRAGMENT",
+        }
+    }
+}
+
+/// Executable locations for the directives defined by the spec, for schemas whose introspection
+/// JSON leaves them out. Directives neither the schema nor the spec define are left alone since
+/// clients commonly add their own (like `@client`).
+fn built_in_directive_locations(directive_name: &str) -> Option<&'static [DirectiveLocation]> {
+    match directive_name {
+        "skip" | "include" => Some(&[
+            DirectiveLocation::Field,
+            DirectiveLocation::FragmentSpread,
+            DirectiveLocation::InlineFragment,
+        ]),
+        "deprecated" | "specifiedBy" => Some(&[]),
+        _ => None,
+    }
+}
+
+#[derive(Debug)]
+pub enum Error {
+    UnknownTypeCondition {
+        type_name: String,
+        position: Pos,
+        possible_type_names: Vec<String>,
+    },
+    FragmentOnNonCompositeType {
+        type_name: String,
+        position: Pos,
+    },
+    ImpossibleFragmentSpread {
+        spread_type_name: String,
+        parent_type_name: String,
+        position: Pos,
+    },
+    FragmentCycle {
+        fragment_names: Vec<String>,
+        position: Pos,
+    },
+    DuplicateArgument {
+        argument_name: String,
+        position: Pos,
+    },
+    DuplicateDirective {
+        directive_name: String,
+        position: Pos,
+    },
+    MisplacedDirective {
+        directive_name: String,
+        location: DirectiveLocation,
+        position: Pos,
+    },
+    DuplicateVariable {
+        variable_name: String,
+        position: Pos,
+    },
+    NonInputVariableType {
+        variable_name: String,
+        type_name: String,
+        position: Pos,
+    },
+    UndefinedVariable {
+        variable_name: String,
+        position: Pos,
+        fragment_name: Option<String>,
+    },
+    UnusedVariable {
+        variable_name: String,
+        position: Pos,
+    },
+    MultipleSubscriptionRootFields(Pos),
+    ConflictingFields {
+        response_name: String,
+        reason: String,
+        position: Pos,
+    },
+}
+
+impl From<(&str, &Path, Error)> for PrintableMessage {
+    fn from((contents, file_path, error): (&str, &Path, Error)) -> Self {
+        match error {
+            Error::UnknownTypeCondition {
+                type_name,
+                position,
+                possible_type_names,
+            } => {
+                let extra = similar_help_suggestions(&type_name, possible_type_names.into_iter())
+                    .unwrap_or_default();
+                PrintableMessage::new_compile_error(
+                    &format!("unknown type `{type_name}` in type condition"),
+                    file_path,
+                    contents,
+                    &position,
+                    Some(&format!("This type does not exist in the schema.{extra}")),
+                )
+            }
+            Error::FragmentOnNonCompositeType {
+                type_name,
+                position,
+            } => PrintableMessage::new_compile_error(
+                &format!("fragment on non-composite type `{type_name}`"),
+                file_path,
+                contents,
+                &position,
+                Some(
+                    "Fragments can only have type conditions on object, interface, or union types.",
+                ),
+            ),
+            Error::ImpossibleFragmentSpread {
+                spread_type_name,
+                parent_type_name,
+                position,
+            } => PrintableMessage::new_compile_error(
+                &format!("impossible fragment spread with type `{spread_type_name}`"),
+                file_path,
+                contents,
+                &position,
+                Some(&format!(
+                    "A value of type `{parent_type_name}` can never be a `{spread_type_name}`, so this spread would never apply."
+                )),
+            ),
+            Error::FragmentCycle {
+                fragment_names,
+                position,
+            } => PrintableMessage::new_compile_error(
+                "fragment spread forms a cycle",
+                file_path,
+                contents,
+                &position,
+                Some(&format!(
+                    "Fragments cannot spread themselves, but this spread leads to `{}`.",
+                    fragment_names.join("` -> `")
+                )),
+            ),
+            Error::DuplicateArgument {
+                argument_name,
+                position,
+            } => PrintableMessage::new_compile_error(
+                &format!("duplicate argument `{argument_name}`"),
+                file_path,
+                contents,
+                &position,
+                Some("Each argument can only be provided once."),
+            ),
+            Error::DuplicateDirective {
+                directive_name,
+                position,
+            } => PrintableMessage::new_compile_error(
+                &format!("duplicate directive `@{directive_name}`"),
+                file_path,
+                contents,
+                &position,
+                Some("This directive can only be used once per location."),
+            ),
+            Error::MisplacedDirective {
+                directive_name,
+                location,
+                position,
+            } => PrintableMessage::new_compile_error(
+                &format!("misplaced directive `@{directive_name}`"),
+                file_path,
+                contents,
+                &position,
+                Some(&format!(
+                    "This directive cannot be used on a {}.",
+                    location.description()
+                )),
+            ),
+            Error::DuplicateVariable {
+                variable_name,
+                position,
+            } => PrintableMessage::new_compile_error(
+                &format!("duplicate variable `${variable_name}`"),
+                file_path,
+                contents,
+                &position,
+                Some("Variable names must be unique within an operation."),
+            ),
+            Error::NonInputVariableType {
+                variable_name,
+                type_name,
+                position,
+            } => PrintableMessage::new_compile_error(
+                &format!("variable `${variable_name}` has non-input type `{type_name}`"),
+                file_path,
+                contents,
+                &position,
+                Some("Variables can only be scalars, enums, or input objects."),
+            ),
+            Error::UndefinedVariable {
+                variable_name,
+                position,
+                fragment_name,
+            } => {
+                let help = match fragment_name {
+                    Some(fragment_name) => format!(
+                        "Fragment `{fragment_name}` uses this variable, but the operation does not declare it."
+                    ),
+                    None => "The operation does not declare this variable.".to_string(),
+                };
+                PrintableMessage::new_compile_error(
+                    &format!("undefined variable `${variable_name}`"),
+                    file_path,
+                    contents,
+                    &position,
+                    Some(&help),
+                )
+            }
+            Error::UnusedVariable {
+                variable_name,
+                position,
+            } => PrintableMessage::new_compile_error(
+                &format!("unused variable `${variable_name}`"),
+                file_path,
+                contents,
+                &position,
+                Some(
+                    "This variable is declared but never used. Remove it or pass it to an argument.",
+                ),
+            ),
+            Error::MultipleSubscriptionRootFields(position) => {
+                PrintableMessage::new_compile_error(
+                    "subscription selects more than one root field",
+                    file_path,
+                    contents,
+                    &position,
+                    Some("Subscriptions must select exactly one top level field."),
+                )
+            }
+            Error::ConflictingFields {
+                response_name,
+                reason,
+                position,
+            } => PrintableMessage::new_compile_error(
+                &format!("conflicting selections for `{response_name}`"),
+                file_path,
+                contents,
+                &position,
+                Some(&format!("{reason}. Use different aliases to select both.")),
+            ),
+        }
+    }
+}
+
+/// A field collected for merge checking, along with the type it was selected on
+#[derive(Debug, Clone, Copy)]
+struct CollectedField<'a, 'b> {
+    parent_type_name: &'a str,
+    field: &'a parsed_query::Field<'b, ParsedTextType>,
+    /// Position of the local spread this field came through, if it came from an imported fragment
+    foreign_spread_position: Option<Pos>,
+}
+
+impl<'a, 'b> CollectedField<'a, 'b> {
+    fn position(&self) -> Pos {
+        self.foreign_spread_position.unwrap_or(self.field.position)
+    }
+
+    fn response_name(&self) -> &'a str {
+        self.field.alias.as_ref().unwrap_or(&self.field.name)
+    }
+}
+
+/// Response names to fields in selection order
+type CollectedFields<'a, 'b> = Vec<(&'a str, Vec<CollectedField<'a, 'b>>)>;
+
+fn insert_collected_field<'a, 'b>(
+    collected: &mut CollectedFields<'a, 'b>,
+    collected_field: CollectedField<'a, 'b>,
+) {
+    let response_name = collected_field.response_name();
+    match collected
+        .iter_mut()
+        .find(|(name, _)| *name == response_name)
+    {
+        Some((_, fields)) => fields.push(collected_field),
+        None => collected.push((response_name, vec![collected_field])),
+    }
+}
+
+#[derive(Debug)]
+struct VariableUsage<'a> {
+    name: &'a str,
+    position: Pos,
+    fragment_name: Option<&'a str>,
+}
+
+fn collect_variables_from_value<'a>(
+    value: &'a parsed_query::Value<'_, ParsedTextType>,
+    names: &mut Vec<&'a str>,
+) {
+    match value {
+        parsed_query::Value::Variable(name) => names.push(name),
+        parsed_query::Value::List(items) => {
+            for item in items {
+                collect_variables_from_value(item, names);
+            }
+        }
+        parsed_query::Value::Object(fields) => {
+            for item in fields.values() {
+                collect_variables_from_value(item, names);
+            }
+        }
+        _ => {}
+    }
+}
+
+fn same_arguments<'a>(first: &Arguments<'a>, second: &Arguments<'a>) -> bool {
+    first.len() == second.len()
+        && first.iter().all(|(name, value)| {
+            second
+                .iter()
+                .any(|(other_name, other_value)| name == other_name && value == other_value)
+        })
+}
+
+fn same_type_shape(first: &schema_field::FieldType, second: &schema_field::FieldType) -> bool {
+    if first.type_modifiers() != second.type_modifiers() {
+        return false;
+    }
+    // Composite types only need the same shape since their sub selections are compared later
+    (first.is_complex() && second.is_complex())
+        || first.reveal_concrete().name == second.reveal_concrete().name
+}
+
+fn spread_names<'a>(selection_set: &'a SelectionSet<'_>, names: &mut Vec<(&'a str, Pos)>) {
+    for selection in &selection_set.items {
+        match selection {
+            parsed_query::Selection::Field(field) => spread_names(&field.selection_set, names),
+            parsed_query::Selection::InlineFragment(inline) => {
+                spread_names(&inline.selection_set, names)
+            }
+            parsed_query::Selection::FragmentSpread(spread) => {
+                names.push((&spread.fragment_name, spread.position))
+            }
+        }
+    }
+}
+
+struct Validator<'a, 'b> {
+    schema: &'a schema::Schema,
+    imported_fragments: &'a ImportedFragments<'b>,
+    local_fragment: Option<&'a parsed_query::FragmentDefinition<'b, ParsedTextType>>,
+    reported_conflicts: HashSet<(Pos, &'a str)>,
+    /// Also check for impossible spreads and unused variables, which compiled fine before there
+    /// was a validation pass
+    strict: bool,
+    errors: Vec<Error>,
+}
+
+impl<'a, 'b> Validator<'a, 'b> {
+    fn lookup_fragment(
+        &self,
+        name: &str,
+    ) -> Option<&'a parsed_query::FragmentDefinition<'b, ParsedTextType>> {
+        self.imported_fragments
+            .get(name)
+            .map(Arc::as_ref)
+            .or_else(|| self.local_fragment.filter(|fragment| fragment.name == name))
+    }
+
+    fn lookup_field(
+        &self,
+        parent_type_name: &str,
+        field_name: &str,
+    ) -> Option<&'a schema_field::Field> {
+        self.schema
+            .get_type_for_name(parent_type_name)?
+            .definition
+            .get_fields_lookup()?
+            .get(field_name)
+    }
+
+    fn is_object_type(&self, type_name: &str) -> bool {
+        matches!(
+            self.schema.get_type_for_name(type_name),
+            Some(schema::Type {
+                definition: schema::TypeDefinition::Object(_),
+                ..
+            })
+        )
+    }
+
+    /// Concrete object types a composite type could be, `None` for non-composite or unknown types
+    fn possible_types(&self, type_name: &'a str) -> Option<HashSet<&'a str>> {
+        match &self.schema.get_type_for_name(type_name)?.definition {
+            schema::TypeDefinition::Object(_) => Some(HashSet::from([type_name])),
+            schema::TypeDefinition::Interface(schema::InterfaceType { possible_types, .. })
+            | schema::TypeDefinition::Union(schema::UnionType { possible_types, .. }) => {
+                Some(possible_types.iter().map(|name| name.as_str()).collect())
+            }
+            _ => None,
+        }
+    }
+
+    /// Returns true if the type condition is usable for further validation
+    fn check_type_condition(&mut self, type_name: &str, position: Pos) -> bool {
+        match self.schema.get_type_for_name(type_name) {
+            None => {
+                self.errors.push(Error::UnknownTypeCondition {
+                    type_name: type_name.to_string(),
+                    position,
+                    possible_type_names: self.schema.type_names().cloned().collect(),
+                });
+                false
+            }
+            Some(schema::Type {
+                definition:
+                    schema::TypeDefinition::Object(_)
+                    | schema::TypeDefinition::Interface(_)
+                    | schema::TypeDefinition::Union(_),
+                ..
+            }) => true,
+            Some(_) => {
+                self.errors.push(Error::FragmentOnNonCompositeType {
+                    type_name: type_name.to_string(),
+                    position,
+                });
+                false
+            }
+        }
+    }
+
+    fn check_spread_possible(
+        &mut self,
+        spread_type_name: &'a str,
+        parent_type_name: &'a str,
+        position: Pos,
+    ) {
+        // Without strictness the IR warns about these as over narrowing instead
+        if !self.strict {
+            return;
+        }
+        if let (Some(spread_possible), Some(parent_possible)) = (
+            self.possible_types(spread_type_name),
+            self.possible_types(parent_type_name),
+        ) {
+            if spread_possible.is_disjoint(&parent_possible) {
+                self.errors.push(Error::ImpossibleFragmentSpread {
+                    spread_type_name: spread_type_name.to_string(),
+                    parent_type_name: parent_type_name.to_string(),
+                    position,
+                });
+            }
+        }
+    }
+
+    fn check_unique_arguments(&mut self, arguments: &Arguments<'_>, position: Pos) {
+        let mut seen = HashSet::with_capacity(arguments.len());
+        for (name, _) in arguments {
+            if !seen.insert(name) {
+                self.errors.push(Error::DuplicateArgument {
+                    argument_name: name.clone(),
+                    position,
+                });
+            }
+        }
+    }
+
+    fn check_directives(
+        &mut self,
+        directives: &[parsed_query::Directive<'_, ParsedTextType>],
+        location: DirectiveLocation,
+    ) {
+        let mut seen = HashSet::with_capacity(directives.len());
+        for directive in directives {
+            self.check_unique_arguments(&directive.arguments, directive.position);
+            // Directives without locations come from incomplete introspection JSON
+            let schema_directive = self.schema.directives().iter().find(|schema_directive| {
+                schema_directive.name == directive.name && !schema_directive.locations.is_empty()
+            });
+            let (is_allowed, is_repeatable) = match schema_directive {
+                Some(schema_directive) => (
+                    schema_directive
+                        .locations
+                        .iter()
+                        .any(|allowed_location| allowed_location == location.introspection_name()),
+                    schema_directive.repeatable,
+                ),
+                None => match built_in_directive_locations(&directive.name) {
+                    Some(locations) => (locations.contains(&location), false),
+                    None => continue,
+                },
+            };
+            if !is_allowed {
+                self.errors.push(Error::MisplacedDirective {
+                    directive_name: directive.name.clone(),
+                    location,
+                    position: directive.position,
+                });
+            }
+            if !is_repeatable && !seen.insert(&directive.name) {
+                self.errors.push(Error::DuplicateDirective {
+                    directive_name: directive.name.clone(),
+                    position: directive.position,
+                });
+            }
+        }
+    }
+
+    /// Validates a selection set, and every selection set nested below it, of the local document
+    fn visit_selection_set(
+        &mut self,
+        selection_set: &'a SelectionSet<'b>,
+        parent_type_name: &'a str,
+    ) {
+        let mut collected = Vec::new();
+        self.collect_fields(
+            selection_set,
+            parent_type_name,
+            None,
+            &mut HashSet::new(),
+            &mut collected,
+        );
+        for (response_name, fields) in &collected {
+            for (index, first) in fields.iter().enumerate() {
+                for second in &fields[index + 1..] {
+                    self.check_field_pair(response_name, *first, *second, false);
+                }
+            }
+        }
+        self.visit_selections(selection_set, parent_type_name);
+    }
+
+    fn visit_selections(&mut self, selection_set: &'a SelectionSet<'b>, parent_type_name: &'a str) {
+        for selection in &selection_set.items {
+            match selection {
+                parsed_query::Selection::Field(field) => {
+                    self.check_unique_arguments(&field.arguments, field.position);
+                    self.check_directives(&field.directives, DirectiveLocation::Field);
+                    if field.selection_set.items.is_empty() {
+                        continue;
+                    }
+                    if let Some(field_def) = self.lookup_field(parent_type_name, &field.name) {
+                        let field_type_name = &field_def.type_description.reveal_concrete().name;
+                        self.visit_selection_set(&field.selection_set, field_type_name);
+                    }
+                }
+                parsed_query::Selection::InlineFragment(inline) => {
+                    self.check_directives(&inline.directives, DirectiveLocation::InlineFragment);
+                    let type_name = match &inline.type_condition {
+                        Some(parsed_query::TypeCondition::On(type_name)) => {
+                            if !self.check_type_condition(type_name, inline.position) {
+                                continue;
+                            }
+                            self.check_spread_possible(
+                                type_name,
+                                parent_type_name,
+                                inline.position,
+                            );
+                            type_name
+                        }
+                        None => parent_type_name,
+                    };
+                    // Fields of inline fragments were already collected by the enclosing selection set
+                    self.visit_selections(&inline.selection_set, type_name);
+                }
+                parsed_query::Selection::FragmentSpread(spread) => {
+                    self.check_directives(&spread.directives, DirectiveLocation::FragmentSpread);
+                    // Unknown fragments are reported by the IR, which has better suggestions
+                    if let Some(fragment) = self.lookup_fragment(&spread.fragment_name) {
+                        let parsed_query::TypeCondition::On(type_name) = &fragment.type_condition;
+                        self.check_spread_possible(type_name, parent_type_name, spread.position);
+                    }
+                }
+            }
+        }
+    }
+
+    fn collect_fields(
+        &self,
+        selection_set: &'a SelectionSet<'b>,
+        parent_type_name: &'a str,
+        foreign_spread_position: Option<Pos>,
+        visited_fragments: &mut HashSet<&'a str>,
+        collected: &mut CollectedFields<'a, 'b>,
+    ) {
+        for selection in &selection_set.items {
+            match selection {
+                parsed_query::Selection::Field(field) => {
+                    let collected_field = CollectedField {
+                        parent_type_name,
+                        field,
+                        foreign_spread_position,
+                    };
+                    insert_collected_field(collected, collected_field);
+                }
+                parsed_query::Selection::InlineFragment(inline) => {
+                    let type_name = match &inline.type_condition {
+                        Some(parsed_query::TypeCondition::On(type_name)) => type_name,
+                        None => parent_type_name,
+                    };
+                    self.collect_fields(
+                        &inline.selection_set,
+                        type_name,
+                        foreign_spread_position,
+                        visited_fragments,
+                        collected,
+                    );
+                }
+                parsed_query::Selection::FragmentSpread(spread) => {
+                    let fragment = match self.lookup_fragment(&spread.fragment_name) {
+                        Some(fragment) => fragment,
+                        None => continue,
+                    };
+                    if !visited_fragments.insert(&fragment.name) {
+                        continue;
+                    }
+                    let parsed_query::TypeCondition::On(type_name) = &fragment.type_condition;
+                    self.collect_fields(
+                        &fragment.selection_set,
+                        type_name,
+                        foreign_spread_position.or(Some(spread.position)),
+                        visited_fragments,
+                        collected,
+                    );
+                }
+            }
+        }
+    }
+
+    fn push_conflict(&mut self, response_name: &'a str, position: Pos, reason: String) {
+        if self.reported_conflicts.insert((position, response_name)) {
+            self.errors.push(Error::ConflictingFields {
+                response_name: response_name.to_string(),
+                reason,
+                position,
+            });
+        }
+    }
+
+    fn check_field_pair(
+        &mut self,
+        response_name: &'a str,
+        first: CollectedField<'a, 'b>,
+        second: CollectedField<'a, 'b>,
+        parents_exclusive: bool,
+    ) {
+        // Conflicts inside a single imported fragment are reported when compiling that fragment
+        if first.foreign_spread_position.is_some()
+            && first.foreign_spread_position == second.foreign_spread_position
+        {
+            return;
+        }
+        let position = second.position();
+        let are_exclusive = parents_exclusive
+            || (first.parent_type_name != second.parent_type_name
+                && self.is_object_type(first.parent_type_name)
+                && self.is_object_type(second.parent_type_name));
+        if !are_exclusive {
+            if first.field.name != second.field.name {
+                let reason = format!(
+                    "`{}` and `{}` are different fields",
+                    first.field.name, second.field.name,
+                );
+                return self.push_conflict(response_name, position, reason);
+            }
+            if !same_arguments(&first.field.arguments, &second.field.arguments) {
+                let reason = "They have differing arguments".to_string();
+                return self.push_conflict(response_name, position, reason);
+            }
+        }
+
+        let (first_def, second_def) = match (
+            self.lookup_field(first.parent_type_name, &first.field.name),
+            self.lookup_field(second.parent_type_name, &second.field.name),
+        ) {
+            (Some(first_def), Some(second_def)) => (first_def, second_def),
+            _ => return,
+        };
+        if !same_type_shape(&first_def.type_description, &second_def.type_description) {
+            let reason = format!(
+                "They return conflicting types `{}` and `{}`",
+                first_def.type_description.reveal_concrete().name,
+                second_def.type_description.reveal_concrete().name,
+            );
+            return self.push_conflict(response_name, position, reason);
+        }
+
+        if first.field.selection_set.items.is_empty() || second.field.selection_set.items.is_empty()
+        {
+            return;
+        }
+        let mut first_sub_fields = Vec::new();
+        self.collect_fields(
+            &first.field.selection_set,
+            &first_def.type_description.reveal_concrete().name,
+            first.foreign_spread_position,
+            &mut HashSet::new(),
+            &mut first_sub_fields,
+        );
+        let mut second_sub_fields = Vec::new();
+        self.collect_fields(
+            &second.field.selection_set,
+            &second_def.type_description.reveal_concrete().name,
+            second.foreign_spread_position,
+            &mut HashSet::new(),
+            &mut second_sub_fields,
+        );
+        for (sub_response_name, first_subs) in &first_sub_fields {
+            let second_subs = match second_sub_fields
+                .iter()
+                .find(|(name, _)| name == sub_response_name)
+            {
+                Some((_, second_subs)) => second_subs,
+                None => continue,
+            };
+            for first_sub in first_subs {
+                for second_sub in second_subs {
+                    self.check_field_pair(
+                        sub_response_name,
+                        *first_sub,
+                        *second_sub,
+                        are_exclusive,
+                    );
+                }
+            }
+        }
+    }
+
+    fn find_fragment_cycle(
+        &self,
+        fragment_name: &'a str,
+        path: &mut Vec<&'a str>,
+        explored: &mut HashSet<&'a str>,
+    ) -> Option<Vec<String>> {
+        if let Some(index) = path.iter().position(|name| *name == fragment_name) {
+            let mut cycle: Vec<_> = path[index..].iter().map(|name| name.to_string()).collect();
+            cycle.push(fragment_name.to_string());
+            return Some(cycle);
+        }
+        if explored.contains(fragment_name) {
+            return None;
+        }
+        let fragment = self.lookup_fragment(fragment_name)?;
+        let mut next_names = Vec::new();
+        spread_names(&fragment.selection_set, &mut next_names);
+        path.push(fragment_name);
+        let cycle = next_names
+            .into_iter()
+            .find_map(|(next_name, _)| self.find_fragment_cycle(next_name, path, explored));
+        path.pop();
+        explored.insert(fragment_name);
+        cycle
+    }
+
+    fn check_fragment_cycles(
+        &mut self,
+        selection_set: &'a SelectionSet<'b>,
+        local_fragment_name: Option<&'a str>,
+    ) {
+        let mut local_spreads = Vec::new();
+        spread_names(selection_set, &mut local_spreads);
+        let mut explored = HashSet::new();
+        for (fragment_name, position) in local_spreads {
+            let mut path: Vec<_> = local_fragment_name.into_iter().collect();
+            if let Some(fragment_names) =
+                self.find_fragment_cycle(fragment_name, &mut path, &mut explored)
+            {
+                self.errors.push(Error::FragmentCycle {
+                    fragment_names,
+                    position,
+                });
+            }
+        }
+    }
+
+    fn collect_variable_usages(
+        &self,
+        selection_set: &'a SelectionSet<'b>,
+        foreign_spread: Option<(Pos, &'a str)>,
+        visited_fragments: &mut HashSet<&'a str>,
+        usages: &mut Vec<VariableUsage<'a>>,
+    ) {
+        let mut push_usages = |arguments: &'a Arguments<'b>, position: Pos| {
+            let mut names = Vec::new();
+            for (_, value) in arguments {
+                collect_variables_from_value(value, &mut names);
+            }
+            usages.extend(names.into_iter().map(|name| VariableUsage {
+                name,
+                position: foreign_spread.map_or(position, |(spread_position, _)| spread_position),
+                fragment_name: foreign_spread.map(|(_, fragment_name)| fragment_name),
+            }));
+        };
+        let mut sub_selection_sets = Vec::new();
+        for selection in &selection_set.items {
+            match selection {
+                parsed_query::Selection::Field(field) => {
+                    push_usages(&field.arguments, field.position);
+                    for directive in &field.directives {
+                        push_usages(&directive.arguments, directive.position);
+                    }
+                    sub_selection_sets.push((&field.selection_set, foreign_spread));
+                }
+                parsed_query::Selection::InlineFragment(inline) => {
+                    for directive in &inline.directives {
+                        push_usages(&directive.arguments, directive.position);
+                    }
+                    sub_selection_sets.push((&inline.selection_set, foreign_spread));
+                }
+                parsed_query::Selection::FragmentSpread(spread) => {
+                    for directive in &spread.directives {
+                        push_usages(&directive.arguments, directive.position);
+                    }
+                    if let Some(fragment) = self.lookup_fragment(&spread.fragment_name) {
+                        if visited_fragments.insert(&fragment.name) {
+                            let foreign_spread =
+                                foreign_spread.or(Some((spread.position, fragment.name.as_str())));
+                            sub_selection_sets.push((&fragment.selection_set, foreign_spread));
+                        }
+                    }
+                }
+            }
+        }
+        for (sub_selection_set, foreign_spread) in sub_selection_sets {
+            self.collect_variable_usages(
+                sub_selection_set,
+                foreign_spread,
+                visited_fragments,
+                usages,
+            );
+        }
+    }
+
+    fn check_variables(
+        &mut self,
+        variable_definitions: &'a [parsed_query::VariableDefinition<'b, ParsedTextType>],
+        directives: &'a [parsed_query::Directive<'b, ParsedTextType>],
+        selection_set: &'a SelectionSet<'b>,
+    ) {
+        let mut defined = HashSet::with_capacity(variable_definitions.len());
+        for definition in variable_definitions {
+            if !defined.insert(definition.name.as_str()) {
+                self.errors.push(Error::DuplicateVariable {
+                    variable_name: definition.name.clone(),
+                    position: definition.position,
+                });
+            }
+            let mut var_type = &definition.var_type;
+            let type_name = loop {
+                match var_type {
+                    parsed_query::Type::NamedType(name) => break name,
+                    parsed_query::Type::ListType(inner)
+                    | parsed_query::Type::NonNullType(inner) => {
+                        var_type = inner;
+                    }
+                }
+            };
+            // Unknown types are reported by the variable IR
+            if let Some(schema::Type {
+                definition:
+                    schema::TypeDefinition::Object(_)
+                    | schema::TypeDefinition::Interface(_)
+                    | schema::TypeDefinition::Union(_),
+                ..
+            }) = self.schema.get_type_for_name(type_name)
+            {
+                self.errors.push(Error::NonInputVariableType {
+                    variable_name: definition.name.clone(),
+                    type_name: type_name.clone(),
+                    position: definition.position,
+                });
+            }
+        }
+
+        let mut usages = Vec::new();
+        for directive in directives {
+            let mut names = Vec::new();
+            for (_, value) in &directive.arguments {
+                collect_variables_from_value(value, &mut names);
+            }
+            usages.extend(names.into_iter().map(|name| VariableUsage {
+                name,
+                position: directive.position,
+                fragment_name: None,
+            }));
+        }
+        self.collect_variable_usages(selection_set, None, &mut HashSet::new(), &mut usages);
+
+        let mut reported = HashSet::new();
+        for usage in &usages {
+            if !defined.contains(usage.name) && reported.insert((usage.name, usage.position)) {
+                self.errors.push(Error::UndefinedVariable {
+                    variable_name: usage.name.to_string(),
+                    position: usage.position,
+                    fragment_name: usage.fragment_name.map(|name| name.to_string()),
+                });
+            }
+        }
+        if !self.strict {
+            return;
+        }
+        let used: HashSet<_> = usages.iter().map(|usage| usage.name).collect();
+        for definition in variable_definitions {
+            if !used.contains(definition.name.as_str()) {
+                self.errors.push(Error::UnusedVariable {
+                    variable_name: definition.name.clone(),
+                    position: definition.position,
+                });
+            }
+        }
+    }
+
+    fn check_single_subscription_root_field(
+        &mut self,
+        selection_set: &'a SelectionSet<'b>,
+        root_type_name: &'a str,
+    ) {
+        let mut collected = Vec::new();
+        self.collect_fields(
+            selection_set,
+            root_type_name,
+            None,
+            &mut HashSet::new(),
+            &mut collected,
+        );
+        if let Some((_, fields)) = collected.get(1) {
+            self.errors
+                .push(Error::MultipleSubscriptionRootFields(fields[0].position()));
+        }
+    }
+}
+
+/// Checks the spec validation rules the IR builder does not cover. Only the local definition is
+/// validated; imported fragments are consulted but their own problems are reported when they are
+/// compiled themselves. Impossible fragment spreads and unused variables are only errors when
+/// `strict`.
+pub fn validate<'a, 'b>(
+    definition: &'a parsed_query::Definition<'b, ParsedTextType>,
+    schema: &'a schema::Schema,
+    imported_fragments: &'a ImportedFragments<'b>,
+    strict: bool,
+) -> Result<(), Vec<Error>> {
+    let mut validator = Validator {
+        schema,
+        imported_fragments,
+        local_fragment: None,
+        reported_conflicts: HashSet::new(),
+        strict,
+        errors: Vec::new(),
+    };
+    match definition {
+        parsed_query::Definition::Fragment(fragment) => {
+            validator.local_fragment = Some(fragment);
+            validator.check_directives(&fragment.directives, DirectiveLocation::FragmentDefinition);
+            let parsed_query::TypeCondition::On(type_name) = &fragment.type_condition;
+            if validator.check_type_condition(type_name, fragment.position) {
+                validator.visit_selection_set(&fragment.selection_set, type_name);
+            }
+            validator.check_fragment_cycles(&fragment.selection_set, Some(&fragment.name));
+        }
+        parsed_query::Definition::Operation(operation) => {
+            let (root_type_name, location, variable_definitions, directives, selection_set) =
+                match operation {
+                    parsed_query::OperationDefinition::Query(query) => (
+                        schema.query_type_name(),
+                        DirectiveLocation::Query,
+                        &query.variable_definitions,
+                        &query.directives,
+                        &query.selection_set,
+                    ),
+                    parsed_query::OperationDefinition::Mutation(mutation) => (
+                        schema.mutation_type_name(),
+                        DirectiveLocation::Mutation,
+                        &mutation.variable_definitions,
+                        &mutation.directives,
+                        &mutation.selection_set,
+                    ),
+                    parsed_query::OperationDefinition::Subscription(subscription) => (
+                        schema.subscription_type_name(),
+                        DirectiveLocation::Subscription,
+                        &subscription.variable_definitions,
+                        &subscription.directives,
+                        &subscription.selection_set,
+                    ),
+                    // The IR reports this as unsupported
+                    parsed_query::OperationDefinition::SelectionSet(_) => return Ok(()),
+                };
+            // The IR reports operations the schema has no root type for
+            let Some(root_type_name) = root_type_name else {
+                return Ok(());
+            };
+            validator.check_directives(directives, location);
+            if location == DirectiveLocation::Subscription {
+                validator.check_single_subscription_root_field(selection_set, root_type_name);
+            }
+            validator.visit_selection_set(selection_set, root_type_name);
+            validator.check_fragment_cycles(selection_set, None);
+            validator.check_variables(variable_definitions, directives, selection_set);
+        }
+    }
+    if validator.errors.is_empty() {
+        Ok(())
+    } else {
+        Err(validator.errors)
+    }
+}
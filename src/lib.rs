@@ -0,0 +1,364 @@
+//! QL Compiler (qlc) compiles TypeScript type definitions from GraphQL documents and a schema's
+//! introspection JSON.
+//!
+//! This is the library behind the `qlc` binary, for tools that want to compile documents without
+//! shelling out. Nothing here writes to disk or exits the process: documents are compiled in
+//! memory and problems are returned as [`Diagnostic`]s.
+//!
+//! ```no_run
+//! use std::path::Path;
+//!
+//! let schema = qlc::load_schema(Path::new("schema.json")).expect("schema should load");
+//! let config = qlc::CompileConfigBuilder::new("src").with_custom_scalars().build();
+//! let compile = qlc::compile_file(&config, &schema, Path::new("src/user_query.graphql"));
+//! for diagnostic in &compile.diagnostics {
+//!     eprintln!("{diagnostic}");
+//! }
+//! if let Some(output) = compile.output {
+//!     println!("{output}");
+//! }
+//! ```
+#![deny(rust_2018_idioms)]
+
+mod cli;
+mod graphql;
+mod lsp;
+mod typescript;
+mod worker_pool;
+
+use cli::PrintableMessage;
+use graphql::fragment_cache::FragmentCache;
+use graphql::ContentsCompile;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::io::Read;
+use std::path::Path;
+
+pub use graphql::schema::Schema;
+pub use graphql::{CompileConfig, CompileConfigBuilder};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Warning,
+    Error,
+}
+
+/// A warning or error about a schema or document. Its `Display` implementation renders it the
+/// same way the `qlc` binary does.
+#[derive(Debug)]
+pub struct Diagnostic(PrintableMessage);
+
+impl Diagnostic {
+    pub fn severity(&self) -> Severity {
+        if self.0.is_warning() {
+            Severity::Warning
+        } else {
+            Severity::Error
+        }
+    }
+
+    pub fn message(&self) -> &str {
+        self.0.message()
+    }
+
+    pub fn file_path(&self) -> Option<&Path> {
+        self.0.file_path()
+    }
+
+    /// 1-based line number the diagnostic points at
+    pub fn line(&self) -> Option<usize> {
+        self.0.line_and_column().map(|(line, _)| line)
+    }
+
+    /// 1-based column number the diagnostic points at
+    pub fn column(&self) -> Option<usize> {
+        self.0.line_and_column().and_then(|(_, column)| column)
+    }
+
+    pub fn help_text(&self) -> Option<&str> {
+        self.0.help_text()
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+/// The result of compiling a single document
+#[derive(Debug)]
+pub struct Compile {
+    /// TypeScript definitions for the document, present when it compiled without errors
+    pub output: Option<String>,
+    /// Names of the global types (enums and input objects) the output imports from the global
+    /// types module, see [`compile_global_types`]
+    pub global_types_used: HashSet<String>,
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+impl Compile {
+    pub fn has_errors(&self) -> bool {
+        self.diagnostics
+            .iter()
+            .any(|diagnostic| diagnostic.severity() == Severity::Error)
+    }
+}
+
+/// Read and process the introspection JSON at `path`
+pub fn load_schema(path: &Path) -> Result<Schema, Vec<Diagnostic>> {
+    graphql::schema::parse_schema(path, None).map_err(into_diagnostics)
+}
+
+/// Process the contents of an introspection JSON file
+pub fn parse_schema(contents: &[u8]) -> Result<Schema, Vec<Diagnostic>> {
+    graphql::schema::parse_schema_contents(contents).map_err(into_diagnostics)
+}
+
+/// Compile a document given its `contents`, which need not match what is on disk at `path`.
+/// Fragments it imports are read from disk relative to `path`.
+///
+/// Unlike the binary, which knows every document in the tree, a spread of a fragment that is not
+/// imported cannot suggest where to import it from.
+pub fn compile_document(
+    config: &CompileConfig,
+    schema: &Schema,
+    path: &Path,
+    contents: &str,
+) -> Compile {
+    let ContentsCompile {
+        report,
+        output,
+        unused_imports,
+    } = graphql::compile_contents(path, contents, config, schema, &FragmentCache::new());
+    let mut diagnostics = into_diagnostics(report.messages);
+    diagnostics.extend(
+        report
+            .unresolved_spreads
+            .into_iter()
+            .map(|unresolved_spread| {
+                Diagnostic(unresolved_spread.into_message(config, &HashMap::new()))
+            }),
+    );
+    diagnostics.extend(
+        unused_imports
+            .iter()
+            .map(|(line_index, line, fragment_name)| {
+                Diagnostic(graphql::unused_import_message(
+                    path,
+                    *line_index,
+                    line,
+                    fragment_name,
+                ))
+            }),
+    );
+    let (output, global_types_used) = match output {
+        Some(the_compile) => (Some(the_compile.contents), the_compile.global_types_used),
+        None => (None, HashSet::new()),
+    };
+    Compile {
+        output,
+        global_types_used,
+        diagnostics,
+    }
+}
+
+/// Read and compile the document at `path`
+pub fn compile_file(config: &CompileConfig, schema: &Schema, path: &Path) -> Compile {
+    match std::fs::read_to_string(path) {
+        Ok(contents) => compile_document(config, schema, path, &contents),
+        Err(io_error) => Compile {
+            output: None,
+            global_types_used: HashSet::new(),
+            diagnostics: vec![Diagnostic(
+                PrintableMessage::new_compile_error_from_read_io_error(&io_error, path),
+            )],
+        },
+    }
+}
+
+/// Compile the global types module for the union of [`Compile::global_types_used`] across
+/// documents
+pub fn compile_global_types(
+    config: &CompileConfig,
+    schema: &Schema,
+    global_types_used: &HashSet<String>,
+) -> Result<String, Diagnostic> {
+    typescript::compile_globals(config, schema, global_types_used)
+        .map(|the_compile| the_compile.contents)
+        .map_err(|error| Diagnostic(error.into()))
+}
+
+fn into_diagnostics(messages: Vec<PrintableMessage>) -> Vec<Diagnostic> {
+    messages.into_iter().map(Diagnostic).collect()
+}
+
+/// Entry point of the `qlc` binary
+#[doc(hidden)]
+pub fn run_cli() -> ! {
+    let config = cli::RuntimeConfig::from_cli();
+    match config.command() {
+        Some(cli::Command::Lsp { .. }) => {
+            // Stdout belongs to the protocol, so messages go to stderr
+            if let Err(messages) = lsp::run(config) {
+                for message in messages {
+                    eprintln!("{message}\n");
+                }
+                std::process::exit(1);
+            }
+            std::process::exit(0);
+        }
+        Some(cli::Command::Compile {
+            stdin, filename, ..
+        }) => run_compile_command(&config, filename, *stdin),
+        Some(cli::Command::Schema { command }) => run_schema_command(&config, command),
+        None => {}
+    }
+    let printer = cli::MessagePrinter::new(config.sort_diagnostics(), config.diagnostics_format());
+    let schema_result = graphql::schema::parse_schema(
+        &config.schema_file_path(),
+        config.cache_dir_path().as_deref(),
+    );
+    match schema_result {
+        Ok(schema) => {
+            let worker_pool = worker_pool::WorkerPool::new(config, schema);
+            worker_pool.work(&printer);
+        }
+        Err(schema_errors) => printer.report(schema_errors),
+    }
+    printer.finish();
+}
+
+/// Print the changes from the `old` to the `new` schema, and report the documents under
+/// `documents` that the new schema breaks or changes the generated types of
+fn run_schema_diff(
+    config: &cli::RuntimeConfig,
+    printer: &cli::MessagePrinter,
+    old_path: &Path,
+    new_path: &Path,
+    documents: Option<&Path>,
+) -> Result<(), Vec<PrintableMessage>> {
+    let old = graphql::schema::parse_schema(old_path, None)?;
+    let new = graphql::schema::parse_schema(new_path, None)?;
+    let changes = graphql::schema::diff::diff_schemas(&old, &new);
+    for change in &changes {
+        match config.diagnostics_format() {
+            cli::DiagnosticsFormat::Human => println!("{change}"),
+            cli::DiagnosticsFormat::Json => println!("{}", change.to_json()),
+        }
+    }
+    if let Some(documents) = documents {
+        let compile_config = CompileConfig::from(config);
+        printer.report(graphql::schema::diff::document_messages(
+            &compile_config,
+            &old,
+            &new,
+            documents,
+        ));
+    }
+    let breaking_count = changes.iter().filter(|change| change.is_breaking()).count();
+    if breaking_count > 0 {
+        let plural = if breaking_count > 1 { "s" } else { "" };
+        return Err(vec![PrintableMessage::new_simple_compile_error(&format!(
+            "{breaking_count} breaking schema change{plural}"
+        ))]);
+    }
+    Ok(())
+}
+
+/// Print the schema at `schema_path`, or the configured schema, as SDL to `output` or stdout
+fn run_schema_print(
+    config: &cli::RuntimeConfig,
+    schema_path: Option<&Path>,
+    output: Option<&Path>,
+) -> Result<(), Vec<PrintableMessage>> {
+    let schema = match schema_path {
+        Some(schema_path) => graphql::schema::parse_schema(schema_path, None)?,
+        None => graphql::schema::parse_schema(
+            &config.schema_file_path(),
+            config.cache_dir_path().as_deref(),
+        )?,
+    };
+    let sdl = graphql::schema::sdl::print_schema(&schema);
+    match output {
+        Some(output) => std::fs::write(output, sdl).map_err(|io_error| {
+            vec![PrintableMessage::new_compile_error_from_write_io_error(
+                &io_error, output,
+            )]
+        }),
+        None => {
+            print!("{sdl}");
+            Ok(())
+        }
+    }
+}
+
+fn run_schema_command(config: &cli::RuntimeConfig, command: &cli::SchemaCommand) -> ! {
+    let printer = cli::MessagePrinter::new(false, config.diagnostics_format());
+    let result = match command {
+        cli::SchemaCommand::Fetch {
+            url,
+            output,
+            headers,
+        } => graphql::schema::fetch::fetch_schema_to_file(
+            config,
+            url.as_deref(),
+            output.as_deref(),
+            headers,
+        ),
+        cli::SchemaCommand::Diff {
+            old,
+            new,
+            documents,
+        } => run_schema_diff(config, &printer, old, new, documents.as_deref()),
+        cli::SchemaCommand::Print { schema, output } => {
+            run_schema_print(config, schema.as_deref(), output.as_deref())
+        }
+    };
+    if let Err(messages) = result {
+        printer.report(messages);
+    }
+    printer.finish();
+}
+
+/// Compile the single document at `path`, or the document read from stdin as if it were at
+/// `path`, printing the TypeScript that would be written next to it to stdout
+fn run_compile_command(config: &cli::RuntimeConfig, path: &Path, read_stdin: bool) -> ! {
+    // Stdout belongs to the compiled output, so messages go to stderr
+    let printer = cli::MessagePrinter::new(false, config.diagnostics_format()).on_stderr();
+    let schema_result = graphql::schema::parse_schema(
+        &config.schema_file_path(),
+        config.cache_dir_path().as_deref(),
+    );
+    match schema_result {
+        Ok(schema) => {
+            let compile_config = CompileConfig::from(config);
+            let compile = if read_stdin {
+                let mut contents = String::new();
+                match std::io::stdin().read_to_string(&mut contents) {
+                    Ok(_) => compile_document(&compile_config, &schema, path, &contents),
+                    Err(io_error) => {
+                        printer.report(vec![PrintableMessage::new_simple_program_error(&format!(
+                            "could not read stdin: {io_error}"
+                        ))]);
+                        printer.finish();
+                    }
+                }
+            } else {
+                compile_file(&compile_config, &schema, path)
+            };
+            if let Some(output) = compile.output {
+                print!("{output}");
+            }
+            printer.report(
+                compile
+                    .diagnostics
+                    .into_iter()
+                    .map(|diagnostic| diagnostic.0)
+                    .collect(),
+            );
+        }
+        Err(schema_errors) => printer.report(schema_errors),
+    }
+    printer.finish();
+}
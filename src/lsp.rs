@@ -0,0 +1,517 @@
+//! Language server for editors, speaking the Language Server Protocol over stdio
+use crate::cli::{PrintableMessage, RuntimeConfig};
+use crate::graphql::fragment_cache::{CachedImport, FragmentCache, ParsedImport};
+use crate::graphql::schema::{parse_schema, Schema};
+use crate::graphql::{
+    canonical_path, compile_contents, get_file_path_of_fragment, graphql_file_paths, import_lines,
+    unused_import_message, CompileConfig, ContentsCompile, ParsedTextType,
+};
+use graphql_parser::query::{Definition, FragmentDefinition};
+use lsp_server::{Connection, ErrorCode, Message, Notification, Request, Response};
+use lsp_types::notification::{
+    DidChangeTextDocument, DidCloseTextDocument, DidOpenTextDocument, Notification as _,
+    PublishDiagnostics,
+};
+use lsp_types::request::{Completion, GotoDefinition, HoverRequest, Request as _};
+use lsp_types::{
+    CompletionOptions, Diagnostic, DiagnosticSeverity, HoverProviderCapability, OneOf, Position,
+    PublishDiagnosticsParams, Range, ServerCapabilities, TextDocumentSyncCapability,
+    TextDocumentSyncKind, Url,
+};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+mod completion;
+mod cursor;
+mod definition;
+mod hover;
+
+type ImportedFragment = (PathBuf, Arc<FragmentDefinition<'static, ParsedTextType>>);
+
+struct Server {
+    compile_config: CompileConfig,
+    schema: Schema,
+    /// Contents of the documents open in the editor, which may not be saved yet
+    open_documents: HashMap<Url, String>,
+    /// Imported documents, with open GraphQL documents resolving to their unsaved contents
+    /// instead of disk. Kept across requests, so only a changed document is parsed again.
+    fragment_cache: FragmentCache,
+    /// Name of the fragment defined by every document under the root directory that defines one,
+    /// by canonical path
+    fragment_names: BTreeMap<PathBuf, String>,
+}
+
+fn protocol_error(error: impl std::fmt::Display) -> Vec<PrintableMessage> {
+    vec![PrintableMessage::new_simple_program_error(&format!(
+        "language server protocol error: {error}"
+    ))]
+}
+
+fn server_capabilities() -> ServerCapabilities {
+    ServerCapabilities {
+        text_document_sync: Some(TextDocumentSyncCapability::Kind(TextDocumentSyncKind::FULL)),
+        completion_provider: Some(CompletionOptions {
+            trigger_characters: Some(vec![String::from("{"), String::from("(")]),
+            ..CompletionOptions::default()
+        }),
+        hover_provider: Some(HoverProviderCapability::Simple(true)),
+        definition_provider: Some(OneOf::Left(true)),
+        ..ServerCapabilities::default()
+    }
+}
+
+/// Canonical path of a document, the key every document is tracked by however the editor spells
+/// its path
+fn document_path(uri: &Url) -> Option<PathBuf> {
+    uri.to_file_path().ok().map(|path| canonical_path(&path))
+}
+
+fn fragment_name_of_contents(contents: &str) -> Option<String> {
+    match graphql_parser::parse_query::<ParsedTextType>(contents)
+        .ok()?
+        .definitions
+        .pop()?
+    {
+        Definition::Fragment(fragment_def) => Some(fragment_def.name),
+        Definition::Operation(_) => None,
+    }
+}
+
+/// Zero-based LSP position of a one-based line and column of `contents`, counting the column in
+/// UTF-16 code units as LSP does by default
+fn lsp_position(contents: &str, line_number: usize, column: usize) -> Position {
+    let line_index = line_number.saturating_sub(1);
+    let line = contents.lines().nth(line_index).unwrap_or("");
+    let character: usize = line
+        .chars()
+        .take(column.saturating_sub(1))
+        .map(char::len_utf16)
+        .sum();
+    Position::new(line_index as u32, character as u32)
+}
+
+fn lsp_position_of_offset(contents: &str, offset: usize) -> Position {
+    let before = &contents[..offset];
+    let line_index = before.matches('\n').count();
+    let line_start = before.rfind('\n').map_or(0, |index| index + 1);
+    let character: usize = before[line_start..].chars().map(char::len_utf16).sum();
+    Position::new(line_index as u32, character as u32)
+}
+
+fn lsp_range_of_offsets(contents: &str, start: usize, end: usize) -> Range {
+    Range::new(
+        lsp_position_of_offset(contents, start),
+        lsp_position_of_offset(contents, end),
+    )
+}
+
+/// Byte offset of an LSP position in `contents`, clamped to the end of its line
+fn offset_of_lsp_position(contents: &str, position: Position) -> usize {
+    let mut line_start = 0;
+    for _ in 0..position.line {
+        match contents[line_start..].find('\n') {
+            Some(index) => line_start += index + 1,
+            None => return contents.len(),
+        }
+    }
+    let line = contents[line_start..].lines().next().unwrap_or("");
+    let mut utf16_count = 0;
+    for (index, c) in line.char_indices() {
+        if utf16_count >= position.character as usize {
+            return line_start + index;
+        }
+        utf16_count += c.len_utf16();
+    }
+    line_start + line.len()
+}
+
+/// Diagnostic for a message about `contents`, underlining the name the message points at
+fn lsp_diagnostic(message: &PrintableMessage, contents: &str) -> Diagnostic {
+    let range = match message.line_and_column() {
+        Some((line_number, Some(column))) => {
+            let start = lsp_position(contents, line_number, column);
+            let line = contents
+                .lines()
+                .nth(line_number.saturating_sub(1))
+                .unwrap_or("");
+            let name_length: usize = line
+                .chars()
+                .skip(column.saturating_sub(1))
+                .take_while(|c| *c == '_' || c.is_ascii_alphanumeric())
+                .map(char::len_utf16)
+                .sum();
+            let end = Position::new(start.line, start.character + name_length.max(1) as u32);
+            Range::new(start, end)
+        }
+        Some((line_number, None)) => {
+            let start = lsp_position(contents, line_number, 1);
+            let line_length: usize = contents
+                .lines()
+                .nth(line_number.saturating_sub(1))
+                .unwrap_or("")
+                .chars()
+                .map(char::len_utf16)
+                .sum();
+            Range::new(start, Position::new(start.line, line_length as u32))
+        }
+        None => Range::default(),
+    };
+    let severity = if message.is_warning() {
+        DiagnosticSeverity::WARNING
+    } else {
+        DiagnosticSeverity::ERROR
+    };
+    let text = match message.help_text() {
+        Some(help_text) => format!("{}\n{help_text}", message.message()),
+        None => message.message().to_string(),
+    };
+    Diagnostic {
+        range,
+        severity: Some(severity),
+        source: Some(String::from("qlc")),
+        message: text,
+        ..Diagnostic::default()
+    }
+}
+
+impl Server {
+    fn new(runtime_config: &RuntimeConfig, schema: Schema) -> Self {
+        let root_dir = runtime_config.root_dir_path();
+        let mut paths = Vec::new();
+        graphql_file_paths(&fs::canonicalize(&root_dir).unwrap_or(root_dir), &mut paths);
+        let fragment_cache = FragmentCache::new();
+        let fragment_names = paths
+            .into_iter()
+            .filter_map(|path| {
+                let loaded = fragment_cache.get_or_load(&path).ok()?;
+                match &loaded.parsed {
+                    ParsedImport::Fragment(fragment_def) => {
+                        Some((canonical_path(&path), fragment_def.name.clone()))
+                    }
+                    ParsedImport::NotAFragment | ParsedImport::Unparseable => None,
+                }
+            })
+            .collect();
+        Server {
+            compile_config: CompileConfig::from(runtime_config),
+            schema,
+            open_documents: HashMap::new(),
+            fragment_cache,
+            fragment_names,
+        }
+    }
+
+    /// Index of fragment names to the documents that define them
+    fn fragment_index(&self) -> HashMap<String, Vec<PathBuf>> {
+        let mut fragment_index: HashMap<_, Vec<_>> = HashMap::new();
+        for (path, fragment_name) in &self.fragment_names {
+            fragment_index
+                .entry(fragment_name.clone())
+                .or_default()
+                .push(path.clone());
+        }
+        fragment_index
+    }
+
+    /// Update the fragment name of the document at canonical `path`, returning whether it changed
+    fn update_fragment_name(&mut self, path: &Path, contents: Option<&str>) -> bool {
+        let fragment_name = contents.and_then(fragment_name_of_contents);
+        let previous_name = match fragment_name {
+            Some(fragment_name) => self.fragment_names.insert(path.to_owned(), fragment_name),
+            None => self.fragment_names.remove(path),
+        };
+        previous_name.as_ref() != self.fragment_names.get(path)
+    }
+
+    /// Canonical paths of the documents imported by a document, directly or through the
+    /// fragments it imports
+    fn imports(&self, path: &Path, contents: &str) -> Vec<(PathBuf, Arc<CachedImport>)> {
+        fn collect(
+            config: &CompileConfig,
+            fragment_cache: &FragmentCache,
+            path: &Path,
+            contents: &str,
+            seen: &mut HashSet<PathBuf>,
+            imports: &mut Vec<(PathBuf, Arc<CachedImport>)>,
+        ) {
+            let mut current_dir = path.to_owned();
+            current_dir.pop();
+            for (_, line) in import_lines(contents) {
+                let file_path =
+                    canonical_path(&get_file_path_of_fragment(config, line, &current_dir));
+                if !seen.insert(file_path.clone()) {
+                    continue;
+                }
+                let Ok(imported) = fragment_cache.get_or_load(&file_path) else {
+                    continue;
+                };
+                collect(
+                    config,
+                    fragment_cache,
+                    &file_path,
+                    &imported.contents,
+                    seen,
+                    imports,
+                );
+                imports.push((file_path, imported));
+            }
+        }
+        let mut imports = Vec::new();
+        collect(
+            &self.compile_config,
+            &self.fragment_cache,
+            path,
+            contents,
+            &mut HashSet::from([canonical_path(path)]),
+            &mut imports,
+        );
+        imports
+    }
+
+    /// Fragments imported by a document, directly or through the fragments it imports
+    fn imported_fragments(&self, path: &Path, contents: &str) -> Vec<ImportedFragment> {
+        self.imports(path, contents)
+            .into_iter()
+            .filter_map(|(file_path, imported)| match &imported.parsed {
+                ParsedImport::Fragment(fragment_def) => Some((file_path, Arc::clone(fragment_def))),
+                ParsedImport::NotAFragment | ParsedImport::Unparseable => None,
+            })
+            .collect()
+    }
+
+    fn diagnostics(&self, path: &Path, contents: &str) -> Vec<Diagnostic> {
+        let ContentsCompile {
+            report,
+            unused_imports,
+            ..
+        } = compile_contents(
+            path,
+            contents,
+            &self.compile_config,
+            &self.schema,
+            &self.fragment_cache,
+        );
+        let fragment_index = self.fragment_index();
+        let unresolved_spread_messages =
+            report
+                .unresolved_spreads
+                .into_iter()
+                .map(|unresolved_spread| {
+                    unresolved_spread.into_message(&self.compile_config, &fragment_index)
+                });
+        let unused_import_messages =
+            unused_imports
+                .iter()
+                .map(|(line_index, line, fragment_name)| {
+                    unused_import_message(path, *line_index, line, fragment_name)
+                });
+        let canonical_document_path = canonical_path(path);
+        report
+            .messages
+            .into_iter()
+            .chain(unresolved_spread_messages)
+            .chain(unused_import_messages)
+            // Messages about other documents are published when those documents are opened
+            .filter(|message| match message.file_path() {
+                Some(file_path) => canonical_path(file_path) == canonical_document_path,
+                None => true,
+            })
+            .map(|message| lsp_diagnostic(&message, contents))
+            .collect()
+    }
+
+    fn publish_diagnostics(
+        &self,
+        connection: &Connection,
+        uri: Url,
+        diagnostics: Vec<Diagnostic>,
+    ) -> Result<(), Vec<PrintableMessage>> {
+        let params = PublishDiagnosticsParams::new(uri, diagnostics, None);
+        let notification = Notification::new(PublishDiagnostics::METHOD.to_string(), params);
+        connection
+            .sender
+            .send(Message::Notification(notification))
+            .map_err(protocol_error)
+    }
+
+    /// Publish diagnostics for the changed document at canonical `path` and the open documents
+    /// that import it. Every open document is republished when `all` is set, for changes such as
+    /// fragment renames that affect documents spreading fragments they do not import.
+    fn publish_changed_diagnostics(
+        &self,
+        connection: &Connection,
+        path: &Path,
+        all: bool,
+    ) -> Result<(), Vec<PrintableMessage>> {
+        let mut dependents = Vec::new();
+        for (uri, contents) in &self.open_documents {
+            let Some(document_path) = document_path(uri) else {
+                continue;
+            };
+            if document_path == path {
+                // The changed document is published first, ahead of its dependents
+                let diagnostics = self.diagnostics(&document_path, contents);
+                self.publish_diagnostics(connection, uri.clone(), diagnostics)?;
+            } else if all
+                || self
+                    .imports(&document_path, contents)
+                    .iter()
+                    .any(|(import_path, _)| import_path == path)
+            {
+                dependents.push((uri, document_path, contents));
+            }
+        }
+        for (uri, document_path, contents) in dependents {
+            let diagnostics = self.diagnostics(&document_path, contents);
+            self.publish_diagnostics(connection, uri.clone(), diagnostics)?;
+        }
+        Ok(())
+    }
+
+    fn handle_notification(
+        &mut self,
+        connection: &Connection,
+        notification: Notification,
+    ) -> Result<(), Vec<PrintableMessage>> {
+        match notification.method.as_str() {
+            DidOpenTextDocument::METHOD => {
+                let params: lsp_types::DidOpenTextDocumentParams =
+                    serde_json::from_value(notification.params).map_err(protocol_error)?;
+                self.document_changed(
+                    connection,
+                    params.text_document.uri,
+                    params.text_document.text,
+                )
+            }
+            DidChangeTextDocument::METHOD => {
+                let params: lsp_types::DidChangeTextDocumentParams =
+                    serde_json::from_value(notification.params).map_err(protocol_error)?;
+                // Only full document sync is offered, so the last change is the whole document
+                match params.content_changes.into_iter().last() {
+                    Some(change) => {
+                        self.document_changed(connection, params.text_document.uri, change.text)
+                    }
+                    None => Ok(()),
+                }
+            }
+            DidCloseTextDocument::METHOD => {
+                let params: lsp_types::DidCloseTextDocumentParams =
+                    serde_json::from_value(notification.params).map_err(protocol_error)?;
+                let uri = params.text_document.uri;
+                let path = document_path(&uri);
+                self.open_documents.remove(&uri);
+                self.publish_diagnostics(connection, uri, Vec::new())?;
+                let Some(path) = path else {
+                    return Ok(());
+                };
+                // Its importers go back to the contents on disk
+                self.fragment_cache.remove(&path);
+                let contents = fs::read_to_string(&path).ok();
+                let renamed = self.update_fragment_name(&path, contents.as_deref());
+                self.publish_changed_diagnostics(connection, &path, renamed)
+            }
+            _ => Ok(()),
+        }
+    }
+
+    fn document_changed(
+        &mut self,
+        connection: &Connection,
+        uri: Url,
+        contents: String,
+    ) -> Result<(), Vec<PrintableMessage>> {
+        let Some(path) = document_path(&uri) else {
+            self.open_documents.insert(uri, contents);
+            return Ok(());
+        };
+        let mut renamed = false;
+        if path.extension().is_some_and(|x| x == "graphql") {
+            renamed = self.update_fragment_name(&path, Some(&contents));
+            let cached_import = CachedImport::parse(contents.clone(), &path);
+            self.fragment_cache.insert(&path, Arc::new(cached_import));
+        }
+        self.open_documents.insert(uri, contents);
+        self.publish_changed_diagnostics(connection, &path, renamed)
+    }
+
+    fn handle_request(&self, request: Request) -> Response {
+        let id = request.id.clone();
+        let result = match request.method.as_str() {
+            Completion::METHOD => serde_json::from_value(request.params)
+                .map(|params| serde_json::to_value(completion::completion(self, params))),
+            HoverRequest::METHOD => serde_json::from_value(request.params)
+                .map(|params| serde_json::to_value(hover::hover(self, params))),
+            GotoDefinition::METHOD => serde_json::from_value(request.params)
+                .map(|params| serde_json::to_value(definition::definition(self, params))),
+            _ => {
+                return Response::new_err(
+                    id,
+                    ErrorCode::MethodNotFound as i32,
+                    format!("unsupported request `{}`", request.method),
+                )
+            }
+        };
+        match result {
+            Ok(Ok(value)) => Response::new_ok(id, value),
+            Ok(Err(error)) | Err(error) => Response::new_err(
+                id,
+                ErrorCode::InvalidParams as i32,
+                format!("invalid params: {error}"),
+            ),
+        }
+    }
+
+    /// Canonical path and contents of a document, preferring the editor's unsaved contents
+    fn document(&self, uri: &Url) -> Option<(PathBuf, String)> {
+        let path = document_path(uri)?;
+        let contents = match self.open_documents.get(uri) {
+            Some(contents) => contents.clone(),
+            None => fs::read_to_string(&path).ok()?,
+        };
+        Some((path, contents))
+    }
+
+    fn main_loop(&mut self, connection: &Connection) -> Result<(), Vec<PrintableMessage>> {
+        for message in &connection.receiver {
+            match message {
+                Message::Request(request) => {
+                    if connection
+                        .handle_shutdown(&request)
+                        .map_err(protocol_error)?
+                    {
+                        return Ok(());
+                    }
+                    let response = self.handle_request(request);
+                    connection
+                        .sender
+                        .send(Message::Response(response))
+                        .map_err(protocol_error)?;
+                }
+                Message::Notification(notification) => {
+                    self.handle_notification(connection, notification)?;
+                }
+                Message::Response(_) => {}
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Serve the language server over stdio until the client shuts it down
+pub fn run(config: RuntimeConfig) -> Result<(), Vec<PrintableMessage>> {
+    let schema = parse_schema(
+        &config.schema_file_path(),
+        config.cache_dir_path().as_deref(),
+    )?;
+    let (connection, io_threads) = Connection::stdio();
+    let capabilities = serde_json::to_value(server_capabilities()).map_err(protocol_error)?;
+    connection
+        .initialize(capabilities)
+        .map_err(protocol_error)?;
+    let mut server = Server::new(&config, schema);
+    server.main_loop(&connection)?;
+    drop(connection);
+    io_threads.join().map_err(protocol_error)
+}
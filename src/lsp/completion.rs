@@ -0,0 +1,151 @@
+//! Completion of field, argument, fragment and type names
+use super::cursor::{cursor_at, Context};
+use super::{offset_of_lsp_position, Server};
+use crate::graphql::schema::field::Field;
+use crate::graphql::schema::TypeDefinition;
+use crate::graphql::{import_lines, import_path_of_fragment, IMPORT_START};
+use lsp_types::{
+    CompletionItem, CompletionItemKind, CompletionItemTag, CompletionParams, CompletionResponse,
+    Documentation, Position, Range, TextEdit,
+};
+use std::collections::BTreeMap;
+use std::path::Path;
+
+fn field_item(field: &Field) -> CompletionItem {
+    CompletionItem {
+        label: field.name.clone(),
+        kind: Some(CompletionItemKind::FIELD),
+        detail: Some(field.type_description.to_string()),
+        documentation: field.documentation.clone().map(Documentation::String),
+        tags: field
+            .deprecated
+            .then(|| vec![CompletionItemTag::DEPRECATED]),
+        ..CompletionItem::default()
+    }
+}
+
+/// Edit adding an `#import` line for `fragment_path` after the last import of the document
+fn import_edit(server: &Server, path: &Path, contents: &str, fragment_path: &Path) -> TextEdit {
+    let line = import_lines(contents)
+        .last()
+        .map_or(0, |(line_index, _)| line_index + 1);
+    let position = Position::new(line as u32, 0);
+    let import_path = import_path_of_fragment(&server.compile_config, path, fragment_path);
+    TextEdit::new(
+        Range::new(position, position),
+        format!("{IMPORT_START}{import_path}\"\n"),
+    )
+}
+
+fn fragment_items(server: &Server, path: &Path, contents: &str) -> Vec<CompletionItem> {
+    let imported_fragments = server.imported_fragments(path, contents);
+    let mut items = BTreeMap::new();
+    for (fragment_path, fragment_def) in &imported_fragments {
+        items.insert(
+            fragment_def.name.clone(),
+            CompletionItem {
+                label: fragment_def.name.clone(),
+                kind: Some(CompletionItemKind::MODULE),
+                detail: Some(format!("fragment on {}", fragment_def.type_condition)),
+                documentation: Some(Documentation::String(fragment_path.display().to_string())),
+                ..CompletionItem::default()
+            },
+        );
+    }
+    // Fragments that are not imported yet are imported when they are completed
+    for (fragment_path, fragment_name) in &server.fragment_names {
+        if items.contains_key(fragment_name) || fragment_path == path {
+            continue;
+        }
+        items.insert(
+            fragment_name.clone(),
+            CompletionItem {
+                label: fragment_name.clone(),
+                kind: Some(CompletionItemKind::MODULE),
+                documentation: Some(Documentation::String(fragment_path.display().to_string())),
+                additional_text_edits: Some(vec![import_edit(
+                    server,
+                    path,
+                    contents,
+                    fragment_path,
+                )]),
+                ..CompletionItem::default()
+            },
+        );
+    }
+    items.into_values().collect()
+}
+
+fn type_condition_items(server: &Server) -> Vec<CompletionItem> {
+    let mut type_names: Vec<_> = server
+        .schema
+        .type_names()
+        .filter(|type_name| !type_name.starts_with("__"))
+        .filter_map(|type_name| {
+            let schema_type = server.schema.get_type_for_name(type_name)?;
+            let kind = match schema_type.definition {
+                TypeDefinition::Object(_) => CompletionItemKind::CLASS,
+                TypeDefinition::Interface(_) => CompletionItemKind::INTERFACE,
+                TypeDefinition::Union(_) => CompletionItemKind::ENUM,
+                _ => return None,
+            };
+            Some((type_name, kind, &schema_type.documentation))
+        })
+        .collect();
+    type_names.sort_by_key(|(type_name, _, _)| *type_name);
+    type_names
+        .into_iter()
+        .map(|(type_name, kind, documentation)| CompletionItem {
+            label: type_name.clone(),
+            kind: Some(kind),
+            documentation: documentation.clone().map(Documentation::String),
+            ..CompletionItem::default()
+        })
+        .collect()
+}
+
+pub fn completion(server: &Server, params: CompletionParams) -> Option<CompletionResponse> {
+    let uri = &params.text_document_position.text_document.uri;
+    let (path, contents) = server.document(uri)?;
+    let offset = offset_of_lsp_position(&contents, params.text_document_position.position);
+    let cursor = cursor_at(&server.schema, &contents, offset);
+    let items = match cursor.context {
+        Context::Selection { parent_type } => {
+            let fields = server
+                .schema
+                .get_type_for_name(&parent_type)?
+                .definition
+                .get_fields_lookup()?;
+            let mut fields: Vec<_> = fields.values().collect();
+            fields.sort_by(|a, b| a.name.cmp(&b.name));
+            fields.into_iter().map(field_item).collect()
+        }
+        Context::Argument {
+            parent_type,
+            field_name,
+        } => {
+            let field = server
+                .schema
+                .get_type_for_name(&parent_type)?
+                .definition
+                .get_fields_lookup()?
+                .get(&field_name)?;
+            field
+                .arguments
+                .iter()
+                .map(|argument| CompletionItem {
+                    label: argument.name.clone(),
+                    kind: Some(CompletionItemKind::VARIABLE),
+                    detail: Some(argument.type_description.to_string()),
+                    documentation: argument.documentation.clone().map(Documentation::String),
+                    insert_text: Some(format!("{}: ", argument.name)),
+                    ..CompletionItem::default()
+                })
+                .collect()
+        }
+        Context::FragmentSpread => fragment_items(server, &path, &contents),
+        Context::TypeCondition => type_condition_items(server),
+        Context::Other => return None,
+    };
+    Some(CompletionResponse::Array(items))
+}
@@ -0,0 +1,257 @@
+//! Works out what the word under the cursor is in a document that is being edited, and so is
+//! often not parseable. Rather than parsing, the tokens before the cursor are walked to track the
+//! selection sets they open.
+use crate::graphql::schema::Schema;
+use std::ops::Range;
+
+#[derive(Debug, PartialEq, Eq)]
+enum Token<'a> {
+    Name(&'a str),
+    Punctuator(&'a str),
+    Value,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum Context {
+    /// Naming a field of `parent_type`
+    Selection {
+        parent_type: String,
+    },
+    /// Naming an argument of `field_name` on `parent_type`
+    Argument {
+        parent_type: String,
+        field_name: String,
+    },
+    /// Naming the fragment of a spread, after `...`
+    FragmentSpread,
+    /// Naming the type of a type condition, after `on`
+    TypeCondition,
+    Other,
+}
+
+#[derive(Debug)]
+pub struct Cursor<'a> {
+    /// Whole name the cursor is on, possibly empty
+    pub word: &'a str,
+    /// Byte range of `word` in the document
+    pub word_range: Range<usize>,
+    pub context: Context,
+}
+
+fn is_name_char(c: char) -> bool {
+    c == '_' || c.is_ascii_alphanumeric()
+}
+
+fn tokenize(text: &str) -> Vec<Token<'_>> {
+    let mut tokens = Vec::new();
+    let bytes = text.as_bytes();
+    let mut index = 0;
+    while index < bytes.len() {
+        let rest = &text[index..];
+        let c = bytes[index];
+        if c.is_ascii_whitespace() || c == b',' {
+            index += 1;
+        } else if c == b'#' {
+            index += rest.find('\n').unwrap_or(rest.len());
+        } else if let Some(block_string) = rest.strip_prefix("\"\"\"") {
+            index += 3 + block_string
+                .find("\"\"\"")
+                .map_or(block_string.len(), |end| end + 3);
+            tokens.push(Token::Value);
+        } else if c == b'"' {
+            let mut end = 1;
+            let mut escaped = false;
+            for (offset, string_char) in rest[1..].char_indices() {
+                end = offset + 1 + string_char.len_utf8();
+                match string_char {
+                    '\\' if !escaped => escaped = true,
+                    '"' | '\n' if !escaped => break,
+                    _ => escaped = false,
+                }
+            }
+            index += end;
+            tokens.push(Token::Value);
+        } else if c == b'-' || c.is_ascii_digit() {
+            let end = rest[1..]
+                .find(|number_char: char| {
+                    !(number_char.is_ascii_alphanumeric() || matches!(number_char, '.' | '+' | '-'))
+                })
+                .map_or(rest.len(), |end| end + 1);
+            index += end;
+            tokens.push(Token::Value);
+        } else if c == b'_' || c.is_ascii_alphabetic() {
+            let end = rest
+                .find(|name_char| !is_name_char(name_char))
+                .unwrap_or(rest.len());
+            tokens.push(Token::Name(&rest[..end]));
+            index += end;
+        } else if rest.starts_with("...") {
+            tokens.push(Token::Punctuator("..."));
+            index += 3;
+        } else {
+            let len = rest.chars().next().map_or(1, char::len_utf8);
+            tokens.push(Token::Punctuator(&rest[..len]));
+            index += len;
+        }
+    }
+    tokens
+}
+
+/// Name of the type of `field_name` on `parent_type`, unwrapped from any lists and non-nulls
+fn field_type_name(schema: &Schema, parent_type: &str, field_name: &str) -> Option<String> {
+    let field = schema
+        .get_type_for_name(parent_type)?
+        .definition
+        .get_fields_lookup()?
+        .get(field_name)?;
+    Some(field.type_description.reveal_concrete().name.clone())
+}
+
+fn root_type_name(schema: &Schema, keyword: &str) -> Option<String> {
+    match keyword {
+        "query" => schema.query_type_name(),
+        "mutation" => schema.mutation_type_name(),
+        "subscription" => schema.subscription_type_name(),
+        _ => None,
+    }
+    .map(String::from)
+}
+
+/// Context for the start of a word, given all the tokens before it
+fn context_after(schema: &Schema, tokens: &[Token<'_>]) -> Context {
+    // The type of every selection set that is open, `None` when it could not be worked out
+    let mut selection_types: Vec<Option<String>> = Vec::new();
+    // Field that a `(` or `{` at the current selection level belongs to
+    let mut last_field: Option<String> = None;
+    // Type for the next `{` from an operation keyword or a type condition
+    let mut pending_type: Option<String> = None;
+    // The field whose arguments are open, if the open parentheses are a field's
+    let mut argument_field: Option<(String, String)> = None;
+    let mut paren_depth = 0_usize;
+    let mut value_brace_depth = 0_usize;
+
+    for (index, token) in tokens.iter().enumerate() {
+        let previous = index.checked_sub(1).map(|previous| &tokens[previous]);
+        let before_previous = index.checked_sub(2).map(|previous| &tokens[previous]);
+        match token {
+            Token::Name(_) if paren_depth > 0 => {}
+            Token::Name(name) if selection_types.is_empty() => {
+                if previous == Some(&Token::Name("on")) {
+                    pending_type = Some(name.to_string());
+                } else if previous.is_none() || previous == Some(&Token::Punctuator("}")) {
+                    pending_type = root_type_name(schema, name);
+                }
+            }
+            Token::Name(name) => match (previous, before_previous) {
+                // A directive name does not change what the next `{` belongs to
+                (Some(Token::Punctuator("@")), _) => {}
+                (Some(Token::Punctuator("...")), _) if *name != "on" => pending_type = None,
+                (Some(Token::Punctuator("...")), _) => {}
+                (Some(Token::Name("on")), Some(Token::Punctuator("..."))) => {
+                    pending_type = Some(name.to_string());
+                }
+                _ => {
+                    last_field = Some(name.to_string());
+                    pending_type = None;
+                }
+            },
+            Token::Punctuator("...") => {
+                // An inline fragment without a type condition selects on the enclosing type
+                pending_type = selection_types.last().cloned().flatten();
+                last_field = None;
+            }
+            Token::Punctuator("{") if paren_depth > 0 => value_brace_depth += 1,
+            Token::Punctuator("{") => {
+                let selection_type = match (pending_type.take(), selection_types.last()) {
+                    (Some(type_name), _) => Some(type_name),
+                    // Shorthand query, without the `query` keyword
+                    (None, None) if matches!(previous, None | Some(Token::Punctuator("}"))) => {
+                        root_type_name(schema, "query")
+                    }
+                    (None, None) => None,
+                    (None, Some(parent_type)) => parent_type
+                        .as_deref()
+                        .zip(last_field.as_deref())
+                        .and_then(|(parent_type, field_name)| {
+                            field_type_name(schema, parent_type, field_name)
+                        }),
+                };
+                selection_types.push(selection_type);
+                last_field = None;
+            }
+            Token::Punctuator("}") if value_brace_depth > 0 => value_brace_depth -= 1,
+            Token::Punctuator("}") => {
+                selection_types.pop();
+                last_field = None;
+                pending_type = None;
+            }
+            Token::Punctuator("(") => {
+                paren_depth += 1;
+                if paren_depth == 1 {
+                    let is_directive = before_previous == Some(&Token::Punctuator("@"));
+                    argument_field = selection_types
+                        .last()
+                        .cloned()
+                        .flatten()
+                        .zip(last_field.clone())
+                        .filter(|_| !is_directive);
+                }
+            }
+            Token::Punctuator(")") => {
+                paren_depth = paren_depth.saturating_sub(1);
+                if paren_depth == 0 {
+                    argument_field = None;
+                    value_brace_depth = 0;
+                }
+            }
+            Token::Punctuator(_) | Token::Value => {}
+        }
+    }
+
+    let previous = tokens.last();
+    if paren_depth > 0 {
+        return match (argument_field, previous) {
+            (_, Some(Token::Punctuator(":" | "$"))) => Context::Other,
+            (Some((parent_type, field_name)), _) if value_brace_depth == 0 => Context::Argument {
+                parent_type,
+                field_name,
+            },
+            _ => Context::Other,
+        };
+    }
+    let before_previous = tokens.len().checked_sub(2).map(|index| &tokens[index]);
+    match (previous, before_previous) {
+        (Some(Token::Name("on")), Some(Token::Punctuator("...") | Token::Name(_))) => {
+            Context::TypeCondition
+        }
+        (Some(Token::Punctuator("...")), _) if !selection_types.is_empty() => {
+            Context::FragmentSpread
+        }
+        (Some(Token::Punctuator("@" | "$")), _) => Context::Other,
+        _ => match selection_types.last() {
+            Some(Some(parent_type)) => Context::Selection {
+                parent_type: parent_type.clone(),
+            },
+            _ => Context::Other,
+        },
+    }
+}
+
+/// Cursor at byte `offset` of `contents`
+pub fn cursor_at<'a>(schema: &Schema, contents: &'a str, offset: usize) -> Cursor<'a> {
+    let offset = offset.min(contents.len());
+    let start = contents[..offset]
+        .char_indices()
+        .rev()
+        .find(|(_, c)| !is_name_char(*c))
+        .map_or(0, |(index, c)| index + c.len_utf8());
+    let end = contents[offset..]
+        .find(|c| !is_name_char(c))
+        .map_or(contents.len(), |index| offset + index);
+    let context = context_after(schema, &tokenize(&contents[..start]));
+    Cursor {
+        word: &contents[start..end],
+        word_range: start..end,
+        context,
+    }
+}
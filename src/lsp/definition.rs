@@ -0,0 +1,63 @@
+//! Go to definition for `#import` lines and fragment spreads
+use super::cursor::{cursor_at, Context};
+use super::{lsp_position, offset_of_lsp_position, Server};
+use crate::graphql::{get_file_path_of_fragment, import_lines};
+use lsp_types::{GotoDefinitionParams, GotoDefinitionResponse, Location, Position, Range, Url};
+use std::path::Path;
+
+fn location(path: &Path, position: Position) -> Option<Location> {
+    let uri = Url::from_file_path(std::fs::canonicalize(path).ok()?).ok()?;
+    Some(Location::new(uri, Range::new(position, position)))
+}
+
+/// Location of the fragment definition in the document at `path`
+fn fragment_location(path: &Path) -> Option<Location> {
+    let contents = std::fs::read_to_string(path).ok()?;
+    let position = graphql_parser::parse_query::<String>(&contents)
+        .ok()
+        .and_then(|document| match document.definitions.first()? {
+            graphql_parser::query::Definition::Fragment(fragment_def) => Some(lsp_position(
+                &contents,
+                fragment_def.position.line,
+                fragment_def.position.column,
+            )),
+            graphql_parser::query::Definition::Operation(_) => None,
+        })
+        .unwrap_or_default();
+    location(path, position)
+}
+
+pub fn definition(server: &Server, params: GotoDefinitionParams) -> Option<GotoDefinitionResponse> {
+    let uri = &params.text_document_position_params.text_document.uri;
+    let (path, contents) = server.document(uri)?;
+    let position = params.text_document_position_params.position;
+
+    if let Some((_, line)) =
+        import_lines(&contents).find(|(line_index, _)| *line_index == position.line as usize)
+    {
+        let current_dir = path.parent().unwrap_or_else(|| Path::new(""));
+        let file_path = get_file_path_of_fragment(&server.compile_config, line, current_dir);
+        return fragment_location(&file_path).map(GotoDefinitionResponse::Scalar);
+    }
+
+    let offset = offset_of_lsp_position(&contents, position);
+    let cursor = cursor_at(&server.schema, &contents, offset);
+    if cursor.context != Context::FragmentSpread || cursor.word.is_empty() {
+        return None;
+    }
+    let imported = server
+        .imported_fragments(&path, &contents)
+        .into_iter()
+        .find(|(_, fragment_def)| fragment_def.name == cursor.word);
+    if let Some((fragment_path, _)) = imported {
+        return fragment_location(&fragment_path).map(GotoDefinitionResponse::Scalar);
+    }
+    // Not imported, so offer every document that defines a fragment by that name
+    let locations: Vec<_> = server
+        .fragment_names
+        .iter()
+        .filter(|(_, fragment_name)| *fragment_name == cursor.word)
+        .filter_map(|(fragment_path, _)| fragment_location(fragment_path))
+        .collect();
+    (!locations.is_empty()).then_some(GotoDefinitionResponse::Array(locations))
+}
@@ -0,0 +1,82 @@
+//! Documentation, types and deprecations of the names under the cursor
+use super::cursor::{cursor_at, Context};
+use super::{lsp_range_of_offsets, offset_of_lsp_position, Server};
+use crate::graphql::schema::Documentation;
+use lsp_types::{Hover, HoverContents, HoverParams, MarkupContent, MarkupKind};
+
+fn markdown(signature: &str, documentation: &Documentation, deprecation: Option<&str>) -> String {
+    let mut sections = vec![format!("```graphql\n{signature}\n```")];
+    if let Some(documentation) = documentation {
+        sections.push(documentation.clone());
+    }
+    if let Some(deprecation) = deprecation {
+        sections.push(format!("**Deprecated:** {deprecation}"));
+    }
+    sections.join("\n\n")
+}
+
+pub fn hover(server: &Server, params: HoverParams) -> Option<Hover> {
+    let uri = &params.text_document_position_params.text_document.uri;
+    let (_, contents) = server.document(uri)?;
+    let offset = offset_of_lsp_position(&contents, params.text_document_position_params.position);
+    let cursor = cursor_at(&server.schema, &contents, offset);
+    if cursor.word.is_empty() {
+        return None;
+    }
+    let value = match cursor.context {
+        Context::Selection { parent_type } => {
+            let field = server
+                .schema
+                .get_type_for_name(&parent_type)?
+                .definition
+                .get_fields_lookup()?
+                .get(cursor.word)?;
+            let deprecation = field.deprecated.then(|| {
+                field
+                    .deprecation_reason
+                    .as_deref()
+                    .unwrap_or("No longer supported")
+            });
+            markdown(
+                &format!("{parent_type}.{}: {}", field.name, field.type_description),
+                &field.documentation,
+                deprecation,
+            )
+        }
+        Context::Argument {
+            parent_type,
+            field_name,
+        } => {
+            let argument = server
+                .schema
+                .get_type_for_name(&parent_type)?
+                .definition
+                .get_fields_lookup()?
+                .get(&field_name)?
+                .arguments
+                .iter()
+                .find(|argument| argument.name == cursor.word)?;
+            markdown(
+                &format!("{}: {}", argument.name, argument.type_description),
+                &argument.documentation,
+                None,
+            )
+        }
+        Context::TypeCondition => {
+            let schema_type = server.schema.get_type_for_name(cursor.word)?;
+            markdown(cursor.word, &schema_type.documentation, None)
+        }
+        Context::FragmentSpread | Context::Other => return None,
+    };
+    Some(Hover {
+        contents: HoverContents::Markup(MarkupContent {
+            kind: MarkupKind::Markdown,
+            value,
+        }),
+        range: Some(lsp_range_of_offsets(
+            &contents,
+            cursor.word_range.start,
+            cursor.word_range.end,
+        )),
+    })
+}
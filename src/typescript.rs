@@ -10,7 +10,7 @@ use std::collections::{HashMap, HashSet};
 mod field;
 
 const EMPTY: &str = "";
-const HEADER: &str = "/* eslint-disable */
+pub const HEADER: &str = "/* eslint-disable */
 // This file was automatically generated and should not be edited.
 
 ";
@@ -136,9 +136,9 @@ fn enum_def_from_type(
 ) -> String {
     let doc_comment = compile_documentation(documentation, false, 0);
     let mut sorted_values = enum_type
-        .possible_values
+        .values
         .iter()
-        .map(|value| format!("  {value} = \"{value}\","))
+        .map(|value| format!("  {0} = \"{0}\",", value.name))
         .collect::<Vec<String>>();
     sorted_values.sort_unstable();
     let joined = sorted_values.join("\n");
@@ -358,12 +358,17 @@ fn type_definitions_from_complex_field_collection(
         all_fields.push((&possibility.name[..], pos_props));
     }
 
-    // These will represent all the types can can be smooshed down
-    let repeated_possiblities: HashSet<&str> = all_fields
-        .into_iter()
-        .filter(|(_, fields)| common_props == *fields)
-        .map(|(name, _)| name)
-        .collect();
+    // These will represent all the types can can be smooshed down, none when every type should
+    // get its own variant
+    let repeated_possiblities: HashSet<&str> = if config.exhaustive_typename_unions {
+        HashSet::new()
+    } else {
+        all_fields
+            .into_iter()
+            .filter(|(_, fields)| common_props == *fields)
+            .map(|(name, _)| name)
+            .collect()
+    };
 
     let num_repeats = repeated_possiblities.len();
     if num_possible_types == num_repeats {
@@ -1,13 +1,18 @@
-use super::cli::{ExitInformation, PrintableMessage, RuntimeConfig};
+use super::cli::{MessagePrinter, PossibleTypesScope, PrintableMessage, RuntimeConfig};
+use super::graphql::fragment_cache::FragmentCache;
+use super::graphql::possible_types::compile_possible_types_file;
 use super::graphql::schema::Schema;
-use super::graphql::{compile_file, compile_global_types_file, CompileConfig};
+use super::graphql::type_policies::compile_type_policies_file;
+use super::graphql::usage::{write_usage_report, SchemaUsage};
+use super::graphql::{
+    compile_file, compile_global_types_file, duplicate_definition_messages, graphql_dir_entries,
+    CompileConfig, CompileReport, DefinitionSummary, GraphQlDirEntry, UnresolvedSpread,
+};
 use crossbeam_channel as channel;
-use std::collections::HashSet;
-use std::fs;
+use std::collections::{HashMap, HashSet};
 use std::path::{Path, PathBuf};
 use std::sync::atomic::{AtomicUsize, Ordering};
 use std::thread;
-use std::time::Duration;
 
 #[derive(Debug)]
 enum Message {
@@ -18,24 +23,28 @@ enum Message {
 #[derive(Debug)]
 enum WorkResult {
     MoreWork(Vec<Work>),
-    CompileResult {
-        global_types_used: HashSet<String>,
-        messages: Vec<PrintableMessage>,
-    },
+    CompileResult(PathBuf, Box<CompileReport>),
     DirIoError(std::io::Error, PathBuf),
 }
 
 #[derive(Debug)]
 struct WorkAggregateResult {
-    messages: Vec<PrintableMessage>,
     global_types: HashSet<String>,
+    abstract_types: HashSet<String>,
+    definitions: Vec<DefinitionSummary>,
+    unresolved_spreads: Vec<UnresolvedSpread>,
+    /// Schema usages of every document, with the path of the document
+    schema_usages: Vec<(PathBuf, SchemaUsage)>,
 }
 
 impl WorkAggregateResult {
     fn new() -> Self {
         WorkAggregateResult {
-            messages: Vec::new(),
             global_types: HashSet::new(),
+            abstract_types: HashSet::new(),
+            definitions: Vec::new(),
+            unresolved_spreads: Vec::new(),
+            schema_usages: Vec::new(),
         }
     }
 
@@ -43,36 +52,31 @@ impl WorkAggregateResult {
         self.global_types.extend(new_globals);
     }
 
-    fn extend_messages(&mut self, messages: Vec<PrintableMessage>) {
-        self.messages.extend(messages);
-    }
-
-    fn append_message(&mut self, message: PrintableMessage) {
-        self.messages.push(message);
+    /// Index of fragment names to the documents that define them
+    fn fragment_index(&self) -> HashMap<String, Vec<PathBuf>> {
+        let mut fragment_index: HashMap<_, Vec<_>> = HashMap::new();
+        for definition in self.definitions.iter().filter(|d| d.is_fragment) {
+            fragment_index
+                .entry(definition.name.clone())
+                .or_default()
+                .push(definition.path.clone());
+        }
+        fragment_index
     }
 
     fn extend_from(&mut self, aggregate: Self) {
         let Self {
-            messages,
             global_types,
+            abstract_types,
+            definitions,
+            unresolved_spreads,
+            schema_usages,
         } = aggregate;
-        self.extend_messages(messages);
         self.extend_globals(global_types);
-    }
-}
-
-impl From<PrintableMessage> for WorkAggregateResult {
-    fn from(message: PrintableMessage) -> Self {
-        WorkAggregateResult {
-            messages: vec![message],
-            global_types: HashSet::new(),
-        }
-    }
-}
-
-impl ExitInformation for WorkAggregateResult {
-    fn messages(&self) -> &[PrintableMessage] {
-        &self.messages
+        self.abstract_types.extend(abstract_types);
+        self.definitions.extend(definitions);
+        self.unresolved_spreads.extend(unresolved_spreads);
+        self.schema_usages.extend(schema_usages);
     }
 }
 
@@ -84,154 +88,117 @@ enum Work {
 
 impl Work {
     fn run_dir_entry(&self, path: &Path) -> Result<Vec<Work>, std::io::Error> {
-        let mut more_work = Vec::new();
-        for io_entry in fs::read_dir(path)? {
-            let entry = io_entry?;
-            let file_type = entry.file_type()?;
-            if file_type.is_dir() {
-                more_work.push(Work::DirEntry(entry.path()));
-            } else if file_type.is_file() {
-                let path = entry.path();
-                if path.extension().map_or(false, |x| x == "graphql") {
-                    more_work.push(Work::GraphQl(path));
-                }
-            }
-        }
-        Ok(more_work)
+        let entries = graphql_dir_entries(path)?;
+        Ok(entries
+            .into_iter()
+            .map(|entry| match entry {
+                GraphQlDirEntry::Dir(path) => Work::DirEntry(path),
+                GraphQlDirEntry::Document(path) => Work::GraphQl(path),
+            })
+            .collect())
     }
 
-    fn run(&self, config: &CompileConfig, schema: &Schema) -> WorkResult {
+    fn run(
+        &self,
+        config: &CompileConfig,
+        schema: &Schema,
+        fragment_cache: &FragmentCache,
+    ) -> WorkResult {
         match self {
             Work::DirEntry(path) => self
                 .run_dir_entry(path)
                 .map(WorkResult::MoreWork)
                 .unwrap_or_else(|io_error| WorkResult::DirIoError(io_error, path.clone())),
-            Work::GraphQl(path) => compile_file(path, config, schema)
-                .map(|compile_report| WorkResult::CompileResult {
-                    global_types_used: compile_report.global_types_used,
-                    messages: compile_report.messages,
-                })
-                .unwrap_or_else(|messages| WorkResult::CompileResult {
-                    global_types_used: HashSet::new(),
-                    messages,
-                }),
+            Work::GraphQl(path) => WorkResult::CompileResult(
+                path.clone(),
+                Box::new(compile_file(path, config, schema, fragment_cache)),
+            ),
         }
     }
 }
 
 struct Worker<'a> {
     pool: &'a WorkerPool,
-    is_waiting: bool,
-    is_quitting: bool,
     aggregate: WorkAggregateResult,
-    num_waiting: &'a AtomicUsize,
-    num_quitting: &'a AtomicUsize,
+    printer: &'a MessagePrinter,
+    /// Work that has been sent but not finished yet, across all workers
+    pending_work: &'a AtomicUsize,
     tx: channel::Sender<Message>,
     rx: channel::Receiver<Message>,
 }
 
 impl<'a> Worker<'a> {
     fn run(mut self) -> WorkAggregateResult {
-        while let Some(work) = self.pop_work() {
-            match work.run(&self.pool.compile_config, &self.pool.schema) {
-                WorkResult::CompileResult {
-                    global_types_used,
-                    messages,
-                } => {
+        while let Ok(Message::Work(work)) = self.rx.recv() {
+            match work.run(
+                &self.pool.compile_config,
+                &self.pool.schema,
+                &self.pool.fragment_cache,
+            ) {
+                WorkResult::CompileResult(path, compile_report) => {
+                    let CompileReport {
+                        messages,
+                        global_types_used,
+                        definition,
+                        unresolved_spreads,
+                        schema_usages,
+                        abstract_types_used,
+                    } = *compile_report;
                     self.aggregate.extend_globals(global_types_used);
-                    self.aggregate.extend_messages(messages);
+                    self.aggregate.abstract_types.extend(abstract_types_used);
+                    self.printer.report(messages);
+                    self.aggregate.definitions.extend(definition);
+                    self.aggregate.unresolved_spreads.extend(unresolved_spreads);
+                    if self.pool.usage_report_path.is_some() {
+                        self.aggregate.schema_usages.extend(
+                            schema_usages
+                                .into_iter()
+                                .map(|schema_usage| (path.clone(), schema_usage)),
+                        );
+                    }
                 }
                 WorkResult::MoreWork(additional_work) => {
                     for work in additional_work {
-                        self.tx.send(Message::Work(work)).unwrap();
+                        self.send_work(work);
                     }
                 }
                 WorkResult::DirIoError(io_error, path) => {
-                    self.aggregate.append_message(
+                    self.printer.report(vec![
                         PrintableMessage::new_compile_error_from_read_io_error(&io_error, &path),
-                    );
+                    ]);
                 }
             }
+            self.finish_work();
         }
         self.aggregate
     }
 
-    fn pop_work(&mut self) -> Option<Work> {
-        loop {
-            match self.rx.try_recv() {
-                Ok(Message::Work(work)) => {
-                    self.set_waiting(false);
-                    self.set_quitting(false);
-                    return Some(work);
-                }
-                Ok(Message::Quit) => {
-                    self.set_waiting(true);
-                    self.set_quitting(true);
-                    loop {
-                        let nwait = self.num_waiting();
-                        let nquit = self.num_quitting();
-                        let threads = self.pool.thread_count;
-                        // If the number of waiting workers dropped, then abort our attempt to quit.
-                        // Sometimes work will come back.
-                        if nwait < threads {
-                            break;
-                        }
-                        // If all workers are in this quit loop, then we can stop.
-                        if nquit == threads {
-                            return None;
-                        }
-                    }
-                }
-                Err(_) => {
-                    let threads = self.pool.thread_count;
-                    self.set_waiting(true);
-                    self.set_quitting(false);
-                    if self.num_waiting() == threads {
-                        for _ in 0..threads {
-                            self.tx.send(Message::Quit).unwrap();
-                        }
-                    } else {
-                        // This is a bit weird, I know, but we want producers to catch up
-                        // without burning the CPU too hard.
-                        thread::sleep(Duration::from_millis(1));
-                    }
-                }
-            }
-        }
-    }
-
-    fn num_waiting(&self) -> usize {
-        self.num_waiting.load(Ordering::SeqCst)
-    }
-
-    fn num_quitting(&self) -> usize {
-        self.num_quitting.load(Ordering::SeqCst)
-    }
-
-    fn set_waiting(&mut self, desired: bool) {
-        if desired && !self.is_waiting {
-            self.is_waiting = true;
-            self.num_waiting.fetch_add(1, Ordering::SeqCst);
-        } else if !desired && self.is_waiting {
-            self.is_waiting = false;
-            self.num_waiting.fetch_sub(1, Ordering::SeqCst);
-        }
+    fn send_work(&self, work: Work) {
+        // Counted before it is sent (and before the work that produced it is finished) so that
+        // the pending count cannot drop to zero while there is still work in flight.
+        self.pending_work.fetch_add(1, Ordering::SeqCst);
+        self.tx.send(Message::Work(work)).unwrap();
     }
 
-    fn set_quitting(&mut self, desired: bool) {
-        if desired && !self.is_quitting {
-            self.is_quitting = true;
-            self.num_quitting.fetch_add(1, Ordering::SeqCst);
-        } else if !desired && self.is_quitting {
-            self.is_quitting = false;
-            self.num_quitting.fetch_sub(1, Ordering::SeqCst);
+    fn finish_work(&self) {
+        let was_last = self.pending_work.fetch_sub(1, Ordering::SeqCst) == 1;
+        if was_last {
+            for _ in 0..self.pool.thread_count {
+                self.tx.send(Message::Quit).unwrap();
+            }
         }
     }
 }
 
 pub struct WorkerPool {
     compile_config: CompileConfig,
+    fragment_cache: FragmentCache,
     root_dir_path: PathBuf,
+    usage_report_path: Option<PathBuf>,
+    possible_types_scope: Option<PossibleTypesScope>,
+    possible_types_file_path: PathBuf,
+    type_policies_file_path: Option<PathBuf>,
+    key_fields: Vec<String>,
     schema: Schema,
     thread_count: usize,
 }
@@ -240,15 +207,24 @@ impl WorkerPool {
     pub fn new(runtime_config: RuntimeConfig, schema: Schema) -> Self {
         WorkerPool {
             compile_config: CompileConfig::from(&runtime_config),
+            fragment_cache: FragmentCache::new(),
             root_dir_path: runtime_config.root_dir_path(),
+            usage_report_path: runtime_config.usage_report_path().map(Path::to_path_buf),
+            possible_types_scope: runtime_config.possible_types_scope(),
+            possible_types_file_path: runtime_config.possible_types_file_path(),
+            type_policies_file_path: runtime_config.type_policies_file_path(),
+            key_fields: runtime_config.key_fields().to_vec(),
             schema,
             thread_count: runtime_config.thread_count(),
         }
     }
 
-    pub fn work(&self) -> impl ExitInformation {
-        let num_waiting = AtomicUsize::new(0);
-        let num_quitting = AtomicUsize::new(0);
+    /// Compiles every document under the root directory, reporting each file's messages to
+    /// `printer` as soon as it is compiled. Messages that need all documents to be compiled
+    /// first, such as duplicate definitions, are reported at the end, along with writing the usage
+    /// report, possible types and type policies modules when configured.
+    pub fn work(&self, printer: &MessagePrinter) {
+        let pending_work = AtomicUsize::new(1);
 
         let mut aggregate = thread::scope(|s| {
             let (tx, rx) = channel::unbounded();
@@ -259,11 +235,9 @@ impl WorkerPool {
                 .map(|_| {
                     let worker = Worker {
                         pool: self,
-                        num_quitting: &num_quitting,
-                        num_waiting: &num_waiting,
+                        printer,
+                        pending_work: &pending_work,
                         aggregate: WorkAggregateResult::new(),
-                        is_quitting: false,
-                        is_waiting: false,
                         tx: tx.clone(),
                         rx: rx.clone(),
                     };
@@ -278,15 +252,48 @@ impl WorkerPool {
             aggregate
         });
 
+        let fragment_index = aggregate.fragment_index();
+        let unresolved_spreads = std::mem::take(&mut aggregate.unresolved_spreads);
+        let mut messages = unresolved_spreads
+            .into_iter()
+            .map(|unresolved_spread| {
+                unresolved_spread.into_message(&self.compile_config, &fragment_index)
+            })
+            .collect::<Vec<_>>();
+        messages.extend(duplicate_definition_messages(&aggregate.definitions));
+        self.compile_config.prune_compile_cache();
+
         if let Err(global_type_error) = compile_global_types_file(
             &self.root_dir_path,
             &self.compile_config,
             &self.schema,
             &aggregate.global_types,
         ) {
-            aggregate.append_message(global_type_error);
+            messages.push(global_type_error);
         }
-
-        aggregate
+        if let Some(scope) = self.possible_types_scope {
+            if let Err(write_error) = compile_possible_types_file(
+                &self.possible_types_file_path,
+                &self.schema,
+                scope,
+                &aggregate.abstract_types,
+            ) {
+                messages.push(write_error);
+            }
+        }
+        if let Some(type_policies_file_path) = &self.type_policies_file_path {
+            if let Err(write_error) =
+                compile_type_policies_file(type_policies_file_path, &self.schema, &self.key_fields)
+            {
+                messages.push(write_error);
+            }
+        }
+        if let Some(usage_report_path) = &self.usage_report_path {
+            if let Err(write_error) = write_usage_report(usage_report_path, aggregate.schema_usages)
+            {
+                messages.push(write_error);
+            }
+        }
+        printer.report(messages);
     }
 }
@@ -143,3 +143,622 @@ fn compile_with_use_show_deprecation_warnings() {
         .run_for_success()
         .stdout(assertion);
 }
+
+#[test]
+fn compile_with_circular_imports() {
+    let mut harness = TestCommandHarness::default();
+    let dir_path = harness.directory_path();
+    let assertion = contains("error: circular fragment import")
+        .and(contains(
+            "#import \"./circular_b_fragment.graphql\"\n  |         ^",
+        ))
+        .and(contains(format!(
+            "= help: This import leads back to this document through `{}:1` -> `{}:1` -> `{}`.",
+            dir_path.join("circular_a_fragment.graphql").display(),
+            dir_path.join("circular_b_fragment.graphql").display(),
+            dir_path.join("circular_a_fragment.graphql").display(),
+        )))
+        .and(contains("error: failure due to 2 errors"));
+    harness
+        .with_fixture_directory("cli/compile_with_circular_imports")
+        .run_for_failure()
+        .stdout(assertion);
+}
+
+#[cfg(unix)]
+#[test]
+fn compile_with_symlinked_imports() {
+    let mut harness = TestCommandHarness::default();
+    let dir_path = harness.directory_path().to_path_buf();
+    std::os::unix::fs::symlink(&dir_path, dir_path.join("linked")).unwrap();
+    let assertion = contains("error: circular fragment import")
+        .and(contains(
+            "#import \"./linked/user_fragment.graphql\"\n  |         ^",
+        ))
+        .and(contains("error: failure due to 1 error"));
+    harness
+        .with_fixture_directory("cli/compile_with_symlinked_imports")
+        .run_for_failure()
+        .stdout(assertion);
+}
+
+#[test]
+fn compile_with_unused_fragment_imports() {
+    let mut harness = TestCommandHarness::default();
+    let query_path = harness.directory_path().join("operator_query.graphql");
+    let assertion = contains("warning: unused fragment import")
+        .and(contains(format!("{}:3:9", query_path.display())))
+        .and(contains(
+            "= help: `UserEmail` is never spread in this document, so this import can be removed.",
+        ))
+        .and(contains(format!("{}:4:9", query_path.display())))
+        .and(contains(
+            "= help: `UserName` is never spread in this document, so this import can be removed.",
+        ))
+        .and(contains("./manager_fragment.graphql\"\n  |").not());
+    harness
+        .with_fixture_directory("cli/compile_with_unused_fragment_imports")
+        .run_for_success()
+        .stdout(assertion);
+}
+
+#[test]
+fn compile_with_remove_unused_fragment_imports() {
+    let mut harness = TestCommandHarness::default();
+    let query_path = harness.directory_path().join("operator_query.graphql");
+    harness
+        .with_fixture_directory("cli/compile_with_unused_fragment_imports")
+        .with_arg("--remove-unused-fragment-imports")
+        .run_for_success()
+        .stdout(is_empty());
+    let fixed_contents = std::fs::read_to_string(query_path).unwrap();
+    assert!(fixed_contents.starts_with(
+        "# Operator details\n#import \"./manager_fragment.graphql\"\n\nquery Operator {\n"
+    ));
+}
+
+#[test]
+fn compile_with_unimported_spread() {
+    let mut harness = TestCommandHarness::default();
+    let query_path = harness
+        .directory_path()
+        .join("queries/operator_query.graphql");
+    let assertion = contains("error: unknown spread fragment name `UserFields`")
+        .and(contains(format!("{}:4:8", query_path.display())))
+        .and(contains(
+            "= help: This fragment name doesn't appear to be in scope. It can be imported with `#import \"../fragments/user_fields_fragment.graphql\"`.",
+        ));
+    harness
+        .with_fixture_directory("cli/compile_with_unimported_spread")
+        .run_for_failure()
+        .stdout(assertion);
+}
+
+#[test]
+fn compile_with_unimported_spread_and_root_dir_import_prefix() {
+    TestCommandHarness::default()
+        .with_fixture_directory("cli/compile_with_unimported_spread")
+        .with_arg("--root-dir-import-prefix=@/")
+        .run_for_failure()
+        .stdout(contains(
+            "It can be imported with `#import \"@/fragments/user_fields_fragment.graphql\"`.",
+        ));
+}
+
+#[test]
+fn compile_with_duplicate_definition_names() {
+    let mut harness = TestCommandHarness::default();
+    let dir_path = harness.directory_path();
+    let admin_path = dir_path.join("admin/user_query.graphql");
+    let profile_path = dir_path.join("profile/user_query.graphql");
+    let assertion = contains("error: duplicate definition name `UserQuery`")
+        .and(contains(format!(
+            "--> {}:1:1\n  |\n1 | query UserQuery {{\n  | ^\n  = help: Operation and fragment names must be unique across all documents, but `UserQuery` is also defined at `{}:2:1`.",
+            admin_path.display(),
+            profile_path.display(),
+        )))
+        .and(contains(format!(
+            "--> {}:2:1\n  |\n2 | query UserQuery {{\n  | ^\n  = help: Operation and fragment names must be unique across all documents, but `UserQuery` is also defined at `{}:1:1`.",
+            profile_path.display(),
+            admin_path.display(),
+        )));
+    harness
+        .with_fixture_directory("cli/compile_with_duplicate_definition_names")
+        .run_for_failure()
+        .stdout(assertion);
+}
+
+#[test]
+fn compile_with_cache_dir() {
+    let mut harness = TestCommandHarness::default();
+    let dir_path = harness.directory_path().to_owned();
+    let cache_dir = dir_path.join(".qlc-cache");
+    harness
+        .with_fixture_directory("typescript/enumeration/compile_with_global_types")
+        .with_arg("--cache-dir")
+        .with_arg(&cache_dir);
+    harness.run_for_success().stdout(is_empty());
+    assert_eq!(cache_dir_file_names(&cache_dir, ".json").len(), 1);
+
+    // A warm run restores both the document output and the globals file
+    std::fs::remove_file(dir_path.join("globals_query.graphql.d.ts")).unwrap();
+    std::fs::remove_file(dir_path.join("graphql-globals.ts")).unwrap();
+    harness.run_for_success().stdout(is_empty());
+}
+
+#[test]
+fn compile_with_cache_dir_reuses_entries() {
+    let mut harness = TestCommandHarness::default();
+    let dir_path = harness.directory_path().to_owned();
+    let cache_dir = dir_path.join(".qlc-cache");
+    let query_path = dir_path.join("operator_query.graphql");
+    std::fs::write(
+        &query_path,
+        "query Operator {\n  operator {\n    id\n  }\n}\n",
+    )
+    .unwrap();
+    harness.with_arg("--cache-dir").with_arg(&cache_dir);
+    harness.run_for_success().stdout(is_empty());
+
+    let entry_path = cache_dir.join(&cache_dir_file_names(&cache_dir, ".json")[0]);
+    std::fs::write(
+        entry_path,
+        r#"{"output":"// from cache","global_types_used":[],"abstract_types_used":[],"schema_usages":[],"definition":null}"#,
+    )
+    .unwrap();
+    harness.run_for_success().stdout(is_empty());
+    let output = std::fs::read_to_string(dir_path.join("operator_query.graphql.d.ts")).unwrap();
+    assert_eq!(output, "// from cache");
+
+    // Changing the document invalidates the entry
+    std::fs::write(
+        &query_path,
+        "query Operator {\n  operator {\n    email\n  }\n}\n",
+    )
+    .unwrap();
+    harness.run_for_success().stdout(is_empty());
+    let output = std::fs::read_to_string(dir_path.join("operator_query.graphql.d.ts")).unwrap();
+    assert!(output.contains("readonly email: string;"));
+}
+
+#[test]
+fn compile_with_cache_dir_prunes_unused_entries() {
+    let mut harness = TestCommandHarness::default();
+    let dir_path = harness.directory_path().to_owned();
+    let cache_dir = dir_path.join(".qlc-cache");
+    std::fs::create_dir_all(&cache_dir).unwrap();
+    std::fs::write(cache_dir.join("0123456789abcdef.json"), "{}").unwrap();
+    std::fs::write(cache_dir.join("0123456789abcdef.1234.tmp"), "{").unwrap();
+    std::fs::write(cache_dir.join("notes.json"), "{}").unwrap();
+    harness
+        .with_fixture_directory("typescript/compile_simple_query")
+        .with_arg("--cache-dir")
+        .with_arg(&cache_dir);
+    harness.run_for_success().stdout(is_empty());
+
+    let mut entries = cache_dir_file_names(&cache_dir, ".json");
+    entries.sort_unstable();
+    assert_eq!(entries.len(), 2);
+    assert!(!entries.contains(&String::from("0123456789abcdef.json")));
+    assert_eq!(entries[1], "notes.json");
+    assert!(cache_dir_file_names(&cache_dir, ".tmp").is_empty());
+}
+
+#[test]
+fn compile_with_cache_dir_caches_processed_schema() {
+    let mut harness = TestCommandHarness::default();
+    let dir_path = harness.directory_path().to_owned();
+    let cache_dir = dir_path.join(".qlc-cache");
+    // Files this cache did not write are left alone, even when their names are close
+    std::fs::create_dir_all(&cache_dir).unwrap();
+    std::fs::write(cache_dir.join("schema-backup.bin"), "").unwrap();
+    harness
+        .with_fixture_directory("typescript/compile_simple_query")
+        .with_arg("--cache-dir")
+        .with_arg(&cache_dir);
+    harness.run_for_success().stdout(is_empty());
+    let mut first_schema_entries = cache_dir_file_names(&cache_dir, ".bin");
+    first_schema_entries.retain(|file_name| file_name != "schema-backup.bin");
+    assert_eq!(first_schema_entries.len(), 1);
+
+    harness.run_for_success().stdout(is_empty());
+    let mut second_schema_entries = cache_dir_file_names(&cache_dir, ".bin");
+    second_schema_entries.retain(|file_name| file_name != "schema-backup.bin");
+    assert_eq!(second_schema_entries, first_schema_entries);
+
+    // A changed schema replaces the processed one
+    let schema_path = dir_path.join("schema.json");
+    let mut schema_contents = std::fs::read_to_string(&schema_path).unwrap();
+    schema_contents.push('\n');
+    std::fs::write(&schema_path, schema_contents).unwrap();
+    harness.run_for_success().stdout(is_empty());
+    let mut third_schema_entries = cache_dir_file_names(&cache_dir, ".bin");
+    assert!(third_schema_entries.contains(&String::from("schema-backup.bin")));
+    third_schema_entries.retain(|file_name| file_name != "schema-backup.bin");
+    assert_eq!(third_schema_entries.len(), 1);
+    assert_ne!(third_schema_entries, first_schema_entries);
+}
+
+#[test]
+fn compile_with_cache_dir_keeps_processed_schemas_of_other_schema_files() {
+    let mut harness = TestCommandHarness::default();
+    let dir_path = harness.directory_path().to_owned();
+    let cache_dir = dir_path.join(".qlc-cache");
+    // Another project sharing the cache directory
+    TestCommandHarness::new()
+        .with_command(&["schema", "print"])
+        .with_arg("--schema-path")
+        .with_arg("tests/fixtures/schema/print_schema/schema.json")
+        .with_arg("--cache-dir")
+        .with_arg(&cache_dir)
+        .run_for_success();
+    let other_schema_entries = cache_dir_file_names(&cache_dir, ".bin");
+    assert_eq!(other_schema_entries.len(), 1);
+
+    harness
+        .with_fixture_directory("typescript/compile_simple_query")
+        .with_arg("--cache-dir")
+        .with_arg(&cache_dir);
+    harness.run_for_success().stdout(is_empty());
+    let schema_path = dir_path.join("schema.json");
+    let mut schema_contents = std::fs::read_to_string(&schema_path).unwrap();
+    schema_contents.push('\n');
+    std::fs::write(&schema_path, schema_contents).unwrap();
+    harness.run_for_success().stdout(is_empty());
+    let schema_entries = cache_dir_file_names(&cache_dir, ".bin");
+    assert_eq!(schema_entries.len(), 2);
+    assert!(schema_entries.contains(&other_schema_entries[0]));
+}
+
+fn cache_dir_file_names(cache_dir: &std::path::Path, extension: &str) -> Vec<String> {
+    std::fs::read_dir(cache_dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+        .filter(|file_name| file_name.ends_with(extension))
+        .collect()
+}
+
+#[test]
+fn compile_with_sort_diagnostics() {
+    let mut harness = TestCommandHarness::default();
+    let dir_path = harness.directory_path().to_owned();
+    let expected_locations = [
+        format!(
+            "--> {}:2:3",
+            dir_path.join("account_query.graphql").display()
+        ),
+        format!(
+            "--> {}:3:5",
+            dir_path.join("admin/viewer_query.graphql").display()
+        ),
+        format!(
+            "--> {}:3:5",
+            dir_path.join("profile/viewer_query.graphql").display()
+        ),
+        format!(
+            "--> {}:4:5",
+            dir_path.join("profile/viewer_query.graphql").display()
+        ),
+    ];
+    harness
+        .with_fixture_directory("cli/compile_with_sort_diagnostics")
+        .with_arg("--sort-diagnostics");
+    for _ in 0..3 {
+        let output = harness.run_for_failure().get_output().stdout.clone();
+        let stdout = String::from_utf8(output).unwrap();
+        let positions = expected_locations
+            .iter()
+            .map(|location| stdout.find(location.as_str()).expect(location))
+            .collect::<Vec<_>>();
+        assert!(
+            positions.windows(2).all(|pair| pair[0] < pair[1]),
+            "{stdout}"
+        );
+    }
+}
+
+#[test]
+fn compile_with_json_diagnostics() {
+    let mut harness = TestCommandHarness::default();
+    let file_path = harness.directory_path().join("account_query.graphql");
+    let output = harness
+        .with_fixture_directory("cli/compile_with_json_diagnostics")
+        .with_arg("--diagnostics-format=json")
+        .run_for_failure()
+        .get_output()
+        .stdout
+        .clone();
+    let stdout = String::from_utf8(output).unwrap();
+    let diagnostics: Vec<serde_json::Value> = stdout
+        .lines()
+        .map(|line| serde_json::from_str(line).expect(line))
+        .collect();
+    assert_eq!(diagnostics.len(), 1, "{stdout}");
+    let diagnostic = &diagnostics[0];
+    assert_eq!(diagnostic["severity"], "error");
+    assert_eq!(diagnostic["message"], "unknown field `accountBalance`");
+    assert_eq!(diagnostic["filePath"], file_path.display().to_string());
+    assert_eq!(diagnostic["line"], 2);
+    assert_eq!(diagnostic["column"], 3);
+}
+
+#[test]
+fn command_after_root_dir() {
+    TestCommandHarness::default()
+        .with_arg("lsp")
+        .run()
+        .failure()
+        .stderr(contains("unexpected argument 'lsp'"));
+}
+
+#[test]
+fn compile_command() {
+    let mut harness = TestCommandHarness::default();
+    let dir_path = harness.directory_path().to_owned();
+    harness
+        .with_fixture_directory("cli/compile_command")
+        .with_command(&["compile"])
+        .with_arg("--filename")
+        .with_arg(dir_path.join("operator_query.graphql"))
+        .run_for_success()
+        .stdout(contains("export type OperatorQuery = {").and(contains("readonly firstName:")));
+    assert!(!dir_path.join("__generated__").exists());
+}
+
+#[test]
+fn compile_command_with_json_diagnostics() {
+    let mut harness = TestCommandHarness::default();
+    let dir_path = harness.directory_path().to_owned();
+    let output = harness
+        .with_fixture_directory("cli/compile_command")
+        .with_arg("--diagnostics-format=json")
+        .with_command(&["compile"])
+        .with_arg("--filename")
+        .with_arg(dir_path.join("nickname_query.graphql"))
+        .run()
+        .failure()
+        .stdout(is_empty())
+        .get_output()
+        .stderr
+        .clone();
+    let stderr = String::from_utf8(output).unwrap();
+    let diagnostic: serde_json::Value = serde_json::from_str(stderr.trim()).expect(&stderr);
+    assert_eq!(diagnostic["severity"], "error");
+    assert_eq!(diagnostic["line"], 3);
+}
+
+#[test]
+fn compile_command_with_stdin() {
+    let mut harness = TestCommandHarness::default();
+    let file_path = harness.directory_path().join("unsaved_query.graphql");
+    // Not on disk, but imports still resolve relative to the filename
+    let contents = "#import \"./user_name_fragment.graphql\"
+
+query UnsavedQuery {
+  operator {
+    ...UserNameFragment
+  }
+}
+";
+    harness
+        .with_fixture_directory("cli/compile_command")
+        .with_command(&["compile"])
+        .with_arg("--stdin")
+        .with_arg("--filename")
+        .with_arg(&file_path)
+        .run_with_stdin(contents)
+        .success()
+        .stderr(is_empty())
+        .stdout(contains("export type UnsavedQuery = {").and(contains("readonly lastName:")));
+    assert!(!file_path.exists());
+}
+
+#[test]
+fn compile_command_with_stdin_and_errors() {
+    let mut harness = TestCommandHarness::default();
+    let file_path = harness.directory_path().join("unsaved_query.graphql");
+    let contents = "query UnsavedQuery {
+  operator {
+    nickname
+  }
+}
+";
+    harness
+        .with_fixture_directory("cli/compile_command")
+        .with_command(&["compile"])
+        .with_arg("--stdin")
+        .with_arg("--filename")
+        .with_arg(&file_path)
+        .run_with_stdin(contents)
+        .failure()
+        .stdout(is_empty())
+        .stderr(
+            contains("error: unknown field `nickname`")
+                .and(contains(format!("--> {}:3:5", file_path.display()))),
+        );
+}
+
+#[test]
+fn compile_with_usage_report_and_cache_dir() {
+    let mut harness = TestCommandHarness::default();
+    let dir_path = harness.directory_path().to_owned();
+    let report_path = dir_path.join("usage.json");
+    let cache_dir = dir_path.join(".qlc-cache");
+    harness
+        .with_fixture_directory("cli/compile_with_usage_report")
+        .with_arg("--usage-report")
+        .with_arg(&report_path)
+        .with_arg("--cache-dir")
+        .with_arg(&cache_dir);
+    harness.run_for_success();
+    let report = std::fs::read_to_string(&report_path).unwrap();
+    assert_eq!(cache_dir_file_names(&cache_dir, ".json").len(), 3);
+
+    // Usages of cached documents still make it into the report
+    std::fs::remove_file(&report_path).unwrap();
+    harness.run_for_success();
+    assert_eq!(std::fs::read_to_string(&report_path).unwrap(), report);
+}
+
+#[test]
+fn compile_with_usage_report() {
+    let mut harness = TestCommandHarness::default();
+    let dir_path = harness.directory_path().to_owned();
+    let report_path = dir_path.join("usage.json");
+    harness
+        .with_fixture_directory("cli/compile_with_usage_report")
+        .with_arg("--usage-report")
+        .with_arg(&report_path)
+        .run_for_success();
+    let report: serde_json::Value =
+        serde_json::from_slice(&std::fs::read(&report_path).unwrap()).unwrap();
+    assert_eq!(
+        report["deprecatedInUse"],
+        serde_json::json!(["User.publicRSAKey"])
+    );
+
+    let usages = report["usages"].as_array().unwrap();
+    let coordinates: Vec<_> = usages
+        .iter()
+        .map(|usage| usage["coordinate"].as_str().unwrap())
+        .collect();
+    assert_eq!(
+        coordinates,
+        [
+            "Host.id",
+            "Host.operatingSystem",
+            "Mutation.provisionHost",
+            "OperatingSystem.FREEBSD",
+            "ProvisionHostInput.os",
+            "ProvisionHostPayload.host",
+            "Query.operator",
+            "User.email",
+            "User.id",
+            "User.publicRSAKey",
+        ]
+    );
+
+    let usage = |coordinate: &str| {
+        usages
+            .iter()
+            .find(|usage| usage["coordinate"] == coordinate)
+            .unwrap()
+    };
+    assert_eq!(usage("OperatingSystem.FREEBSD")["kind"], "enumValue");
+    assert_eq!(usage("ProvisionHostInput.os")["kind"], "inputField");
+    // Fields of imported fragments count once, in the fragment's own document
+    let email = usage("User.email");
+    assert_eq!(email["kind"], "field");
+    assert_eq!(email["count"], 2);
+    assert_eq!(
+        email["references"],
+        serde_json::json!([
+            {
+                "filePath": dir_path.join("operator_query.graphql").display().to_string(),
+                "line": 6,
+                "column": 5,
+            },
+            {
+                "filePath": dir_path.join("user_fields_fragment.graphql").display().to_string(),
+                "line": 3,
+                "column": 3,
+            },
+        ])
+    );
+    let public_rsa_key = usage("User.publicRSAKey");
+    assert_eq!(public_rsa_key["deprecated"], true);
+    assert!(public_rsa_key["deprecationReason"].is_string());
+}
+
+#[test]
+fn compile_with_possible_types() {
+    TestCommandHarness::default()
+        .with_arg("--possible-types=used")
+        .with_fixture_directory("cli/compile_with_possible_types")
+        .run_for_success();
+}
+
+#[test]
+fn compile_with_type_policies() {
+    let mut harness = TestCommandHarness::default();
+    let query_path = harness.directory_path().join("operator_query.graphql");
+    let assertion = contains("warning: selection of `manager` is missing key field `id` of `User`")
+        .and(contains(format!("{}:6:5", query_path.display())))
+        .and(contains("warning: selection of `operator`").not());
+    harness
+        .with_arg("--type-policies")
+        .with_fixture_directory("cli/compile_with_type_policies")
+        .run_for_success()
+        .stdout(assertion);
+}
+
+#[test]
+fn compile_with_type_policies_without_warnings() {
+    TestCommandHarness::default()
+        .with_default_rc_file_contents(r#"{ "typePolicies": true, "warnMissingKeyFields": false }"#)
+        .with_fixture_directory("cli/compile_with_type_policies")
+        .run_for_success()
+        .stdout(is_empty());
+}
+
+#[test]
+fn compile_with_warn_missing_key_fields() {
+    let mut harness = TestCommandHarness::default();
+    let query_path = harness.directory_path().join("node_query.graphql");
+    let assertion = contains("warning: selection of `host` is missing key field `id` of `Host`")
+        .and(contains(format!("{}:7:3", query_path.display())))
+        .and(contains(
+            "= help: Apollo normalizes `Host` objects by `id` in its cache, without it they are stored under their parent and can go stale. Select `id` here or in a spread fragment.",
+        ))
+        .and(contains("warning: selection of `network`").not())
+        .and(contains("warning: selection of `node`").not())
+        .and(contains("warning: 1 warning emitted"));
+    harness
+        .with_arg("--warn-missing-key-fields")
+        .with_fixture_directory("cli/compile_with_warn_missing_key_fields")
+        .run_for_success()
+        .stdout(assertion);
+}
+
+#[test]
+fn compile_with_warn_missing_key_fields_aliases() {
+    let mut harness = TestCommandHarness::default();
+    let query_path = harness.directory_path().join("aliased_key_query.graphql");
+    // Only a selection whose response key is the key field counts
+    let assertion =
+        contains("warning: selection of `operator` is missing key field `id` of `User`")
+            .and(contains(format!("{}:2:3", query_path.display())))
+            .and(contains(
+                "warning: selection of `network` is missing key field `id` of `Network`",
+            ))
+            .and(contains(format!("{}:6:3", query_path.display())))
+            .and(contains("warning: selection of `host`").not())
+            .and(contains("warning: 2 warnings emitted"));
+    harness
+        .with_arg("--warn-missing-key-fields")
+        .with_fixture_directory("cli/compile_with_warn_missing_key_fields_aliases")
+        .run_for_success()
+        .stdout(assertion);
+}
+
+#[test]
+fn compile_with_possible_types_as_json() {
+    let mut harness = TestCommandHarness::default();
+    let output_path = harness.directory_path().join("possible-types.json");
+    harness
+        .with_default_rc_file_contents(
+            r#"{ "possibleTypes": "all", "possibleTypesFileName": "possible-types.json" }"#,
+        )
+        .run_for_success();
+    let possible_types: serde_json::Value =
+        serde_json::from_slice(&std::fs::read(output_path).unwrap()).unwrap();
+    assert_eq!(
+        possible_types,
+        serde_json::json!({
+            "Node": ["Host", "Network", "User"],
+            "ResourceTag": ["BooleanTag", "JSONTag", "KeyValueTag"],
+            "Tag": ["BooleanTag", "JSONTag", "KeyValueTag"],
+            "Tagged": ["Host", "Network", "User"],
+        })
+    );
+}
@@ -0,0 +1,29 @@
+import type { QueryDocumentNode } from "@notarize/qlc-cli/typed-documentnode";
+
+export type NodeQuery_node_Host_tags_KeyValueTag = {
+  readonly key: string;
+};
+
+export type NodeQuery_node_Host_tags_$$other = {
+
+};
+
+export type NodeQuery_node_Host_tags = NodeQuery_node_Host_tags_KeyValueTag | NodeQuery_node_Host_tags_$$other;
+
+export type NodeQuery_node_Host = {
+  readonly id: string;
+  readonly tags: NodeQuery_node_Host_tags[];
+};
+
+export type NodeQuery_node_$$other = {
+  readonly id: string;
+};
+
+export type NodeQuery_node = NodeQuery_node_Host | NodeQuery_node_$$other;
+
+export type NodeQuery = {
+  readonly node: NodeQuery_node | null;
+};
+
+declare const graphqlDocument: QueryDocumentNode<NodeQuery, never>;
+export default graphqlDocument;
\ No newline at end of file
@@ -0,0 +1,6 @@
+const possibleTypes: Record<string, string[]> = {
+  Node: ["Host", "Network", "User"],
+  ResourceTag: ["BooleanTag", "JSONTag", "KeyValueTag"],
+};
+
+export default possibleTypes;
\ No newline at end of file
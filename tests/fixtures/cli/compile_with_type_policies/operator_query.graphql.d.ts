@@ -0,0 +1,21 @@
+import type { QueryDocumentNode } from "@notarize/qlc-cli/typed-documentnode";
+
+export type OperatorQuery_operator_manager = {
+  readonly firstName: string;
+};
+
+export type OperatorQuery_operator = {
+  readonly email: string;
+  readonly id: string;
+  /**
+   * A user's manager, if they have one
+   */
+  readonly manager: OperatorQuery_operator_manager | null;
+};
+
+export type OperatorQuery = {
+  readonly operator: OperatorQuery_operator | null;
+};
+
+declare const graphqlDocument: QueryDocumentNode<OperatorQuery, never>;
+export default graphqlDocument;
@@ -0,0 +1,32 @@
+import type { TypePolicies } from "@apollo/client";
+
+/**
+ * Key field of every object type, `never` for those without one
+ */
+export type KeyFields = {
+  AttachHostToNetworksPayload: never;
+  BooleanTag: never;
+  Host: "id";
+  HostConnection: never;
+  HostEdge: never;
+  JSONTag: never;
+  KeyValueTag: never;
+  Mutation: never;
+  Network: "id";
+  ProvisionHostPayload: never;
+  PublicKey: never;
+  Query: never;
+  Subscription: never;
+  User: "id";
+  UserActivity: never;
+};
+
+export type ObjectTypename = keyof KeyFields;
+
+const typePolicies: TypePolicies = {
+  Host: { keyFields: ["id"] },
+  Network: { keyFields: ["id"] },
+  User: { keyFields: ["id"] },
+};
+
+export default typePolicies;
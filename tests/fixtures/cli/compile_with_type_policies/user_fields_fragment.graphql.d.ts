@@ -0,0 +1,9 @@
+import type { FragmentDocumentNode } from "@notarize/qlc-cli/typed-documentnode";
+
+export type UserFields = {
+  readonly email: string;
+  readonly id: string;
+};
+
+declare const graphqlDocument: FragmentDocumentNode<UserFields>;
+export default graphqlDocument;
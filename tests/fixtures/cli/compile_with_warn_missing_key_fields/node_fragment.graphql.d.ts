@@ -0,0 +1,8 @@
+import type { FragmentDocumentNode } from "@notarize/qlc-cli/typed-documentnode";
+
+export type NodeIdentity = {
+  readonly id: string;
+};
+
+declare const graphqlDocument: FragmentDocumentNode<NodeIdentity>;
+export default graphqlDocument;
@@ -0,0 +1,24 @@
+import type { QueryDocumentNode } from "@notarize/qlc-cli/typed-documentnode";
+import type { OperatingSystem } from "graphql-globals";
+
+export type NodeQuery_host = {
+  readonly operatingSystem: OperatingSystem;
+};
+
+export type NodeQuery_network = {
+  readonly cidr: string;
+  readonly id: string;
+};
+
+export type NodeQuery_node = {
+  readonly id: string;
+};
+
+export type NodeQuery = {
+  readonly host: NodeQuery_host | null;
+  readonly network: NodeQuery_network | null;
+  readonly node: NodeQuery_node | null;
+};
+
+declare const graphqlDocument: QueryDocumentNode<NodeQuery, never>;
+export default graphqlDocument;
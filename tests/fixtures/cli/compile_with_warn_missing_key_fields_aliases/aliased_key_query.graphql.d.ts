@@ -0,0 +1,24 @@
+import type { QueryDocumentNode } from "@notarize/qlc-cli/typed-documentnode";
+
+export type AliasedKeyQuery_host = {
+  readonly hostId: string;
+  readonly id: string;
+};
+
+export type AliasedKeyQuery_network = {
+  readonly id: string;
+};
+
+export type AliasedKeyQuery_operator = {
+  readonly firstName: string;
+  readonly userId: string;
+};
+
+export type AliasedKeyQuery = {
+  readonly host: AliasedKeyQuery_host | null;
+  readonly network: AliasedKeyQuery_network | null;
+  readonly operator: AliasedKeyQuery_operator | null;
+};
+
+declare const graphqlDocument: QueryDocumentNode<AliasedKeyQuery, never>;
+export default graphqlDocument;
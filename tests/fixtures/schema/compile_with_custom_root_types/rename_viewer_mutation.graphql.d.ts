@@ -0,0 +1,17 @@
+import type { MutationDocumentNode } from "@notarize/qlc-cli/typed-documentnode";
+
+export type RenameViewer_renameViewer = {
+  readonly id: string;
+  readonly name: string;
+};
+
+export type RenameViewer = {
+  readonly renameViewer: RenameViewer_renameViewer;
+};
+
+export type RenameViewerVariables = {
+  name: string;
+};
+
+declare const graphqlDocument: MutationDocumentNode<RenameViewer, RenameViewerVariables>;
+export default graphqlDocument;
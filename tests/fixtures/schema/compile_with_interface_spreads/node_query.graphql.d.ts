@@ -0,0 +1,20 @@
+import type { QueryDocumentNode } from "@notarize/qlc-cli/typed-documentnode";
+
+export type NodeQuery_node_Document = {
+  readonly id: string;
+  readonly url: string;
+};
+
+export type NodeQuery_node_Revision = {
+  readonly id: string;
+  readonly number: number;
+};
+
+export type NodeQuery_node = NodeQuery_node_Document | NodeQuery_node_Revision;
+
+export type NodeQuery = {
+  readonly node: NodeQuery_node | null;
+};
+
+declare const graphqlDocument: QueryDocumentNode<NodeQuery, never>;
+export default graphqlDocument;
\ No newline at end of file
@@ -0,0 +1,9 @@
+import type { FragmentDocumentNode } from "@notarize/qlc-cli/typed-documentnode";
+
+export type ResourceFragment = {
+  readonly id: string;
+  readonly url: string;
+};
+
+declare const graphqlDocument: FragmentDocumentNode<ResourceFragment>;
+export default graphqlDocument;
\ No newline at end of file
@@ -0,0 +1,13 @@
+import type { QueryDocumentNode } from "@notarize/qlc-cli/typed-documentnode";
+
+export type ResourceQuery_resource = {
+  readonly id: string;
+  readonly title: string;
+};
+
+export type ResourceQuery = {
+  readonly resource: ResourceQuery_resource | null;
+};
+
+declare const graphqlDocument: QueryDocumentNode<ResourceQuery, never>;
+export default graphqlDocument;
\ No newline at end of file
@@ -0,0 +1,13 @@
+import type { QueryDocumentNode } from "@notarize/qlc-cli/typed-documentnode";
+
+export type ViewerQuery_viewer = {
+  readonly id: string;
+  readonly name: string;
+};
+
+export type ViewerQuery = {
+  readonly viewer: ViewerQuery_viewer | null;
+};
+
+declare const graphqlDocument: QueryDocumentNode<ViewerQuery, never>;
+export default graphqlDocument;
@@ -1,4 +1,4 @@
-import type { QueryDocumentNode } from "@notarize/qlc-cli/typed-documentnode";
+import type { SubscriptionDocumentNode } from "@notarize/qlc-cli/typed-documentnode";
 
 export type SimpleSubscription_me = {
   readonly firstName: string;
@@ -7,8 +7,11 @@ export type SimpleSubscription_me = {
 };
 
 export type SimpleSubscription = {
+  /**
+   * The current user, pushed whenever they change
+   */
   readonly me: SimpleSubscription_me | null;
 };
 
-declare const graphqlDocument: QueryDocumentNode<SimpleSubscription, never>;
+declare const graphqlDocument: SubscriptionDocumentNode<SimpleSubscription, never>;
 export default graphqlDocument;
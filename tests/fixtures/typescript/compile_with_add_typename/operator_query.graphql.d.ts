@@ -0,0 +1,40 @@
+import type { QueryDocumentNode } from "@notarize/qlc-cli/typed-documentnode";
+
+export type OperatorQuery_operator_personalHost = {
+  readonly __typename: "Host";
+  readonly numCpus: number;
+};
+
+export type OperatorQuery_operator_tags_BooleanTag = {
+  readonly __typename: "BooleanTag";
+};
+
+export type OperatorQuery_operator_tags_JSONTag = {
+  readonly __typename: "JSONTag";
+};
+
+export type OperatorQuery_operator_tags_KeyValueTag = {
+  readonly __typename: "KeyValueTag";
+  readonly key: string;
+};
+
+export type OperatorQuery_operator_tags = OperatorQuery_operator_tags_BooleanTag | OperatorQuery_operator_tags_JSONTag | OperatorQuery_operator_tags_KeyValueTag;
+
+export type OperatorQuery_operator = {
+  readonly __typename: "User";
+  readonly firstName: string;
+  readonly id: string;
+  readonly lastName: string;
+  /**
+   * A user's personal device
+   */
+  readonly personalHost: OperatorQuery_operator_personalHost;
+  readonly tags: OperatorQuery_operator_tags[];
+};
+
+export type OperatorQuery = {
+  readonly operator: OperatorQuery_operator | null;
+};
+
+declare const graphqlDocument: QueryDocumentNode<OperatorQuery, never>;
+export default graphqlDocument;
\ No newline at end of file
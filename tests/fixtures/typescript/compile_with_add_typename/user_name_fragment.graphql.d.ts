@@ -0,0 +1,10 @@
+import type { FragmentDocumentNode } from "@notarize/qlc-cli/typed-documentnode";
+
+export type UserNameFragment = {
+  readonly __typename: "User";
+  readonly firstName: string;
+  readonly lastName: string;
+};
+
+declare const graphqlDocument: FragmentDocumentNode<UserNameFragment>;
+export default graphqlDocument;
\ No newline at end of file
@@ -0,0 +1,26 @@
+import type { QueryDocumentNode } from "@notarize/qlc-cli/typed-documentnode";
+
+export type GetByNodeExhaustive_node_Host = {
+  readonly __typename: "Host";
+  readonly id: string;
+};
+
+export type GetByNodeExhaustive_node_Network = {
+  readonly __typename: "Network";
+  readonly cidr: string;
+  readonly id: string;
+};
+
+export type GetByNodeExhaustive_node_User = {
+  readonly __typename: "User";
+  readonly id: string;
+};
+
+export type GetByNodeExhaustive_node = GetByNodeExhaustive_node_Host | GetByNodeExhaustive_node_Network | GetByNodeExhaustive_node_User;
+
+export type GetByNodeExhaustive = {
+  readonly node: GetByNodeExhaustive_node | null;
+};
+
+declare const graphqlDocument: QueryDocumentNode<GetByNodeExhaustive, never>;
+export default graphqlDocument;
\ No newline at end of file
@@ -0,0 +1,32 @@
+import type { QueryDocumentNode } from "@notarize/qlc-cli/typed-documentnode";
+import type { OperatingSystem } from "graphql-globals";
+
+export type ValidDirectives_host = {
+  readonly id: string;
+  readonly numCpus: number;
+  readonly operatingSystem: OperatingSystem;
+};
+
+export type ValidDirectives_node_User = {
+  readonly email: string;
+  readonly id: string;
+};
+
+export type ValidDirectives_node_$$other = {
+  readonly id: string;
+};
+
+export type ValidDirectives_node = ValidDirectives_node_User | ValidDirectives_node_$$other;
+
+export type ValidDirectives = {
+  readonly host: ValidDirectives_host | null;
+  readonly node: ValidDirectives_node | null;
+};
+
+export type ValidDirectivesVariables = {
+  id: string;
+  withOs: boolean;
+};
+
+declare const graphqlDocument: QueryDocumentNode<ValidDirectives, ValidDirectivesVariables>;
+export default graphqlDocument;
@@ -1,2 +1,4 @@
 pub mod cmd;
+pub mod http;
+pub mod lsp;
 pub mod stdout_predicates;
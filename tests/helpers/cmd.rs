@@ -4,9 +4,11 @@ use assert_fs::prelude::*;
 use predicates::str as p_str;
 use predicates::Predicate;
 use std::env;
+use std::ffi::{OsStr, OsString};
 use std::fs;
+use std::io::Write;
 use std::path::{Path, PathBuf};
-use std::process::{Command, Stdio};
+use std::process::{Child, Command, Stdio};
 
 const DEFAULT_QLCRC_JSON_PATH: &str = ".qlcrc.json";
 const FIXTURE_ROOT_PATH: &str = "tests/fixtures";
@@ -59,6 +61,9 @@ impl<'a, P: Predicate<str>> std::fmt::Display for FixtureSaverPredicate<'a, P> {
 pub struct TestCommandHarness {
     fixture_assert_directory: Option<PathBuf>,
     proc_cmd: Command,
+    /// Subcommand names, which go ahead of every other argument
+    command: Vec<OsString>,
+    args: Vec<OsString>,
     temp_dir: assert_fs::TempDir,
 }
 
@@ -76,7 +81,6 @@ impl TestCommandHarness {
             .into_persistent_if(env::var_os("KEEP_TEST_TEMPDIRS").is_some());
 
         let mut proc_cmd = Command::cargo_bin("qlc").expect("qlc bin failure");
-        proc_cmd.arg("--num-threads=2").arg(temp_dir.path());
 
         // If user adds `--nocapture` arg to `cargo test`, we can show output
         if env::args().any(|arg| arg == "--nocapture") {
@@ -86,6 +90,8 @@ impl TestCommandHarness {
 
         Self {
             proc_cmd,
+            command: Vec::new(),
+            args: Vec::new(),
             temp_dir,
             fixture_assert_directory: None,
         }
@@ -119,8 +125,19 @@ impl TestCommandHarness {
         self
     }
 
-    pub fn with_arg(&mut self, arg: impl AsRef<std::ffi::OsStr>) -> &mut Self {
-        self.proc_cmd.arg(arg);
+    pub fn with_arg(&mut self, arg: impl AsRef<OsStr>) -> &mut Self {
+        self.args.push(arg.as_ref().to_owned());
+        self
+    }
+
+    /// Runs a subcommand, ie `["schema", "fetch"]`, instead of compiling the temp dir
+    pub fn with_command(&mut self, names: &[&str]) -> &mut Self {
+        self.command = names.iter().map(OsString::from).collect();
+        self
+    }
+
+    pub fn with_env(&mut self, key: impl AsRef<OsStr>, value: impl AsRef<OsStr>) -> &mut Self {
+        self.proc_cmd.env(key, value);
         self
     }
 
@@ -159,14 +176,62 @@ impl TestCommandHarness {
         self
     }
 
+    /// The command with all of its arguments, the temp dir being the root directory for all but
+    /// the schema commands. Arguments added after a run are kept for the next one.
+    fn proc_cmd(&mut self) -> &mut Command {
+        if self.proc_cmd.get_args().next().is_none() {
+            let is_schema_command = self.command.first().is_some_and(|name| name == "schema");
+            self.proc_cmd.args(&self.command);
+            // Schema commands neither compile nor take a root directory
+            if !is_schema_command {
+                self.proc_cmd
+                    .arg("--num-threads=2")
+                    .arg(self.temp_dir.path());
+            }
+        }
+        self.proc_cmd.args(self.args.drain(..))
+    }
+
+    /// Starts the command with piped stdin and stdout, for modes like `lsp` that keep running
+    pub fn spawn(&mut self) -> Child {
+        self.proc_cmd()
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .expect("qlc spawn failure")
+    }
+
+    /// Runs the command without asserting anything, for modes that report on stderr
+    pub fn run(&mut self) -> Assert {
+        self.proc_cmd().assert()
+    }
+
+    /// Runs the command with `contents` written to its stdin, without asserting anything
+    pub fn run_with_stdin(&mut self, contents: &str) -> Assert {
+        let mut child = self
+            .proc_cmd()
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .expect("qlc spawn failure");
+        child
+            .stdin
+            .take()
+            .expect("missing stdin")
+            .write_all(contents.as_bytes())
+            .expect("qlc stdin write failure");
+        child.wait_with_output().expect("qlc wait failure").assert()
+    }
+
     pub fn run_for_failure(&mut self) -> Assert {
-        let assert = self.proc_cmd.assert().failure().stderr(p_str::is_empty());
+        let assert = self.proc_cmd().assert().failure().stderr(p_str::is_empty());
         self.assert_fixture_outputs();
         assert
     }
 
     pub fn run_for_success(&mut self) -> Assert {
-        let assert = self.proc_cmd.assert().success().stderr(p_str::is_empty());
+        let assert = self.proc_cmd().assert().success().stderr(p_str::is_empty());
         self.assert_fixture_outputs();
         assert
     }
@@ -180,7 +245,7 @@ impl TestCommandHarness {
         if callback(&config_file_child).is_some() {
             // Since this command's CWD is not the temp dir, use an argument to
             // tell qlc where it is.
-            self.proc_cmd.arg("-c").arg(config_file_child.path());
+            self.with_arg("-c").with_arg(config_file_child.path());
         }
     }
 
@@ -0,0 +1,74 @@
+//! A stub HTTP server for commands that make requests
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::mpsc::{self, Receiver};
+use std::thread;
+use std::time::Duration;
+
+/// Answers every request with the same response, recording the requests it receives
+#[derive(Debug)]
+pub struct StubServer {
+    url: String,
+    requests: Receiver<String>,
+}
+
+impl StubServer {
+    pub fn start(status: u16, body: impl Into<Vec<u8>>) -> Self {
+        let body = body.into();
+        let listener = TcpListener::bind("127.0.0.1:0").expect("stub server bind failure");
+        let url = format!(
+            "http://{}/graphql",
+            listener.local_addr().expect("stub server address failure")
+        );
+        let (sender, requests) = mpsc::channel();
+        thread::spawn(move || {
+            for mut stream in listener.incoming().filter_map(Result::ok) {
+                let request = read_request(&mut stream);
+                let head = format!(
+                    "HTTP/1.1 {status} Stub\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+                    body.len()
+                );
+                let _ = stream
+                    .write_all(head.as_bytes())
+                    .and_then(|_| stream.write_all(&body));
+                if sender.send(request).is_err() {
+                    break;
+                }
+            }
+        });
+        Self { url, requests }
+    }
+
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    /// Head and body of the next request the server received
+    pub fn next_request(&self) -> String {
+        self.requests
+            .recv_timeout(Duration::from_secs(10))
+            .expect("stub server received no request")
+    }
+}
+
+fn read_request(stream: &mut TcpStream) -> String {
+    let mut reader = BufReader::new(stream);
+    let mut head = String::new();
+    loop {
+        let mut line = String::new();
+        match reader.read_line(&mut line) {
+            Ok(0) | Err(_) => break,
+            Ok(_) if line == "\r\n" => break,
+            Ok(_) => head.push_str(&line),
+        }
+    }
+    let content_length = head
+        .lines()
+        .filter_map(|line| line.split_once(':'))
+        .find(|(name, _)| name.eq_ignore_ascii_case("content-length"))
+        .and_then(|(_, value)| value.trim().parse().ok())
+        .unwrap_or(0);
+    let mut body = vec![0; content_length];
+    let _ = reader.read_exact(&mut body);
+    format!("{head}\r\n{}", String::from_utf8_lossy(&body))
+}
@@ -0,0 +1,158 @@
+use crate::helpers::cmd::TestCommandHarness;
+use serde_json::{json, Value};
+use std::collections::VecDeque;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::path::Path;
+use std::process::{Child, ChildStdin, ChildStdout};
+
+/// Minimal client for talking to `qlc lsp` over its stdio
+pub struct LspClient {
+    child: Child,
+    stdin: ChildStdin,
+    stdout: BufReader<ChildStdout>,
+    next_id: u64,
+    /// Notifications received while waiting for a response
+    notifications: VecDeque<Value>,
+}
+
+impl LspClient {
+    /// Starts the language server for the harness and initializes it
+    pub fn start(harness: &mut TestCommandHarness) -> Self {
+        let mut child = harness.with_command(&["lsp"]).spawn();
+        let stdin = child.stdin.take().expect("lsp stdin");
+        let stdout = BufReader::new(child.stdout.take().expect("lsp stdout"));
+        let mut client = LspClient {
+            child,
+            stdin,
+            stdout,
+            next_id: 1,
+            notifications: VecDeque::new(),
+        };
+        client.request(
+            "initialize",
+            json!({ "processId": null, "rootUri": null, "capabilities": {} }),
+        );
+        client.notify("initialized", json!({}));
+        client
+    }
+
+    pub fn uri(path: &Path) -> String {
+        format!("file://{}", path.display())
+    }
+
+    pub fn open(&mut self, path: &Path, text: &str) {
+        self.notify(
+            "textDocument/didOpen",
+            json!({
+                "textDocument": {
+                    "uri": Self::uri(path),
+                    "languageId": "graphql",
+                    "version": 1,
+                    "text": text,
+                }
+            }),
+        );
+    }
+
+    pub fn change(&mut self, path: &Path, version: u64, text: &str) {
+        self.notify(
+            "textDocument/didChange",
+            json!({
+                "textDocument": { "uri": Self::uri(path), "version": version },
+                "contentChanges": [{ "text": text }],
+            }),
+        );
+    }
+
+    pub fn close(&mut self, path: &Path) {
+        self.notify(
+            "textDocument/didClose",
+            json!({ "textDocument": { "uri": Self::uri(path) } }),
+        );
+    }
+
+    /// Sends a request at `position` of the document at `path` and returns its result
+    pub fn request_at(&mut self, method: &str, path: &Path, position: (u32, u32)) -> Value {
+        self.request(
+            method,
+            json!({
+                "textDocument": { "uri": Self::uri(path) },
+                "position": { "line": position.0, "character": position.1 },
+            }),
+        )
+    }
+
+    pub fn request(&mut self, method: &str, params: Value) -> Value {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.send(json!({ "jsonrpc": "2.0", "id": id, "method": method, "params": params }));
+        loop {
+            let message = self.receive();
+            if message.get("id") == Some(&json!(id)) {
+                assert_eq!(message.get("error"), None, "error response to `{method}`");
+                return message["result"].clone();
+            }
+            self.notifications.push_back(message);
+        }
+    }
+
+    pub fn notify(&mut self, method: &str, params: Value) {
+        self.send(json!({ "jsonrpc": "2.0", "method": method, "params": params }));
+    }
+
+    /// Waits for the next diagnostics published for the document at `path`
+    pub fn diagnostics(&mut self, path: &Path) -> Vec<Value> {
+        let uri = Self::uri(path);
+        loop {
+            let message = self
+                .notifications
+                .pop_front()
+                .unwrap_or_else(|| self.receive());
+            if message["method"] == "textDocument/publishDiagnostics"
+                && message["params"]["uri"] == uri
+            {
+                return message["params"]["diagnostics"]
+                    .as_array()
+                    .expect("diagnostics array")
+                    .clone();
+            }
+        }
+    }
+
+    fn send(&mut self, message: Value) {
+        let body = message.to_string();
+        write!(self.stdin, "Content-Length: {}\r\n\r\n{body}", body.len()).expect("lsp write");
+        self.stdin.flush().expect("lsp flush");
+    }
+
+    fn receive(&mut self) -> Value {
+        let mut content_length = None;
+        loop {
+            let mut header = String::new();
+            self.stdout.read_line(&mut header).expect("lsp header read");
+            let header = header.trim_end();
+            if header.is_empty() {
+                break;
+            }
+            if let Some(length) = header.strip_prefix("Content-Length: ") {
+                content_length = Some(length.parse::<usize>().expect("content length"));
+            }
+        }
+        let mut body = vec![0; content_length.expect("missing content length")];
+        self.stdout.read_exact(&mut body).expect("lsp body read");
+        serde_json::from_slice(&body).expect("lsp body json")
+    }
+}
+
+impl Drop for LspClient {
+    fn drop(&mut self) {
+        if !std::thread::panicking() {
+            self.request("shutdown", Value::Null);
+            self.notify("exit", Value::Null);
+            let status = self.child.wait().expect("lsp wait");
+            assert!(status.success(), "lsp exited with {status}");
+        } else {
+            let _ = self.child.kill();
+        }
+    }
+}
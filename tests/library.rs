@@ -0,0 +1,64 @@
+use std::collections::HashSet;
+use std::path::Path;
+
+const DEFAULT_SCHEMA_PATH: &str = "tests/fixtures/schema_generation/output/schema.json";
+const FIXTURE_ROOT_PATH: &str = "tests/fixtures";
+
+fn default_schema() -> qlc::Schema {
+    qlc::load_schema(Path::new(DEFAULT_SCHEMA_PATH)).expect("default schema should load")
+}
+
+fn read_fixture(path: &Path) -> String {
+    std::fs::read_to_string(path).expect("fixture should be readable")
+}
+
+#[test]
+fn compile_file_in_memory() {
+    let fixture_dir =
+        Path::new(FIXTURE_ROOT_PATH).join("typescript/enumeration/compile_with_global_types");
+    let config = qlc::CompileConfigBuilder::new(&fixture_dir).build();
+    let schema = default_schema();
+
+    let compile = qlc::compile_file(&config, &schema, &fixture_dir.join("globals_query.graphql"));
+    assert!(compile.diagnostics.is_empty(), "{:?}", compile.diagnostics);
+    let output = compile.output.expect("document should compile");
+    assert!(
+        output.ends_with(read_fixture(&fixture_dir.join("globals_query.graphql.d.ts")).trim_end())
+    );
+
+    let globals = qlc::compile_global_types(&config, &schema, &compile.global_types_used)
+        .expect("global types should compile");
+    assert!(globals.ends_with(read_fixture(&fixture_dir.join("graphql-globals.ts")).trim_end()));
+}
+
+#[test]
+fn compile_document_with_diagnostics() {
+    let config = qlc::CompileConfigBuilder::new(FIXTURE_ROOT_PATH).build();
+    let schema = default_schema();
+    let path = Path::new(FIXTURE_ROOT_PATH).join("unsaved_query.graphql");
+    let contents = "query UnsavedQuery {\n  operator {\n    nickname\n  }\n}\n";
+
+    let compile = qlc::compile_document(&config, &schema, &path, contents);
+    assert!(compile.has_errors());
+    assert!(compile.output.is_none());
+    assert_eq!(compile.global_types_used, HashSet::new());
+    let diagnostic = &compile.diagnostics[0];
+    assert_eq!(diagnostic.severity(), qlc::Severity::Error);
+    assert_eq!(diagnostic.file_path(), Some(path.as_path()));
+    assert_eq!((diagnostic.line(), diagnostic.column()), (Some(3), Some(5)));
+    assert!(
+        diagnostic.message().contains("`nickname`"),
+        "{}",
+        diagnostic.message()
+    );
+}
+
+#[test]
+fn parse_schema_with_malformed_json() {
+    let errors = qlc::parse_schema(b"{\"data\": {}}")
+        .err()
+        .expect("schema should not parse");
+    assert_eq!(errors.len(), 1);
+    assert!(errors[0].message().starts_with("malformed schema"));
+    assert_eq!(errors[0].file_path(), None);
+}
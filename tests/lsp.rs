@@ -0,0 +1,194 @@
+use crate::helpers::cmd::TestCommandHarness;
+use crate::helpers::lsp::LspClient;
+use serde_json::{json, Value};
+
+/// Zero-based line and character just after the first occurrence of `needle` in `text`
+fn position_after(text: &str, needle: &str) -> (u32, u32) {
+    let offset = text.find(needle).expect("needle in text") + needle.len();
+    let before = &text[..offset];
+    let line = before.matches('\n').count();
+    let character = before.len() - before.rfind('\n').map_or(0, |index| index + 1);
+    (line as u32, character as u32)
+}
+
+fn labels(items: &Value) -> Vec<&str> {
+    items
+        .as_array()
+        .expect("completion items")
+        .iter()
+        .map(|item| item["label"].as_str().expect("label"))
+        .collect()
+}
+
+fn item<'a>(items: &'a Value, label: &str) -> &'a Value {
+    items
+        .as_array()
+        .expect("completion items")
+        .iter()
+        .find(|item| item["label"] == label)
+        .expect("completion item")
+}
+
+#[test]
+fn lsp_publishes_diagnostics_on_open_and_change() {
+    let mut harness = TestCommandHarness::default();
+    harness.with_fixture_directory("lsp");
+    let path = harness.directory_path().join("operator_query.graphql");
+    let text = std::fs::read_to_string(&path).unwrap();
+    let mut client = LspClient::start(&mut harness);
+
+    client.open(&path, &text);
+    let diagnostics = client.diagnostics(&path);
+    assert_eq!(diagnostics.len(), 1, "{diagnostics:?}");
+    assert_eq!(diagnostics[0]["severity"], 1);
+    assert_eq!(
+        diagnostics[0]["range"],
+        json!({ "start": { "line": 6, "character": 4 }, "end": { "line": 6, "character": 12 } })
+    );
+    assert!(diagnostics[0]["message"]
+        .as_str()
+        .unwrap()
+        .contains("nickname"));
+
+    client.change(&path, 2, &text.replace("    nickname\n", ""));
+    assert_eq!(client.diagnostics(&path), Vec::<Value>::new());
+}
+
+#[test]
+fn lsp_completes_fields_arguments_and_fragments() {
+    let mut harness = TestCommandHarness::default();
+    harness.with_fixture_directory("lsp");
+    let path = harness.directory_path().join("operator_query.graphql");
+    let text = "#import \"./user_name_fragment.graphql\"\n\nquery OperatorQuery {\n  network(id: \"1\") {\n    hosts(first: 1, ) {\n      totalCount\n    }\n  }\n  operator {\n    id\n    ...\n  }\n}\n";
+    let mut client = LspClient::start(&mut harness);
+    client.open(&path, text);
+
+    let fields = client.request_at(
+        "textDocument/completion",
+        &path,
+        position_after(text, "    id\n"),
+    );
+    assert!(labels(&fields).contains(&"firstName"));
+    assert_eq!(item(&fields, "publicRSAKey")["tags"], json!([1]));
+    assert_eq!(item(&fields, "manager")["detail"], "User");
+    assert_eq!(item(&fields, "tags")["detail"], "[ResourceTag!]!");
+
+    let arguments = client.request_at(
+        "textDocument/completion",
+        &path,
+        position_after(text, "hosts(first: 1, "),
+    );
+    assert_eq!(labels(&arguments), vec!["first", "last"]);
+
+    let fragments = client.request_at(
+        "textDocument/completion",
+        &path,
+        position_after(text, "    ..."),
+    );
+    assert_eq!(
+        labels(&fragments),
+        vec!["ManagerFragment", "UserNameFragment"]
+    );
+    assert_eq!(
+        item(&fragments, "UserNameFragment")["additionalTextEdits"],
+        Value::Null
+    );
+    assert_eq!(
+        item(&fragments, "ManagerFragment")["additionalTextEdits"],
+        json!([{
+            "range": { "start": { "line": 1, "character": 0 }, "end": { "line": 1, "character": 0 } },
+            "newText": "#import \"./manager_fragment.graphql\"\n",
+        }])
+    );
+}
+
+#[test]
+fn lsp_hovers_fields_with_documentation_and_deprecation() {
+    let mut harness = TestCommandHarness::default();
+    harness.with_fixture_directory("lsp");
+    let path = harness.directory_path().join("operator_query.graphql");
+    let text = "query OperatorQuery {\n  operator {\n    lastLogin\n  }\n}\n";
+    let mut client = LspClient::start(&mut harness);
+    client.open(&path, text);
+
+    let hover = client.request_at("textDocument/hover", &path, (2, 6));
+    assert_eq!(
+        hover["contents"]["value"],
+        "```graphql\nUser.lastLogin: ISO8601\n```\n\nUser's last time logging in\n\n**Deprecated:** Use activity.login instead"
+    );
+    assert_eq!(
+        hover["range"],
+        json!({ "start": { "line": 2, "character": 4 }, "end": { "line": 2, "character": 13 } })
+    );
+    assert_eq!(
+        client.request_at("textDocument/hover", &path, (1, 0)),
+        Value::Null
+    );
+}
+
+#[test]
+fn lsp_goes_to_definition_of_imports_and_spreads() {
+    let mut harness = TestCommandHarness::default();
+    harness.with_fixture_directory("lsp");
+    let path = harness.directory_path().join("operator_query.graphql");
+    let text = std::fs::read_to_string(&path).unwrap();
+    let mut client = LspClient::start(&mut harness);
+    client.open(&path, &text);
+
+    let expected_range =
+        json!({ "start": { "line": 0, "character": 0 }, "end": { "line": 0, "character": 0 } });
+    for position in [(0, 12), position_after(&text, "...UserName")] {
+        let definition = client.request_at("textDocument/definition", &path, position);
+        assert!(definition["uri"]
+            .as_str()
+            .unwrap()
+            .ends_with("/user_name_fragment.graphql"));
+        assert_eq!(definition["range"], expected_range);
+    }
+}
+
+#[test]
+fn lsp_resolves_imports_to_unsaved_contents() {
+    let mut harness = TestCommandHarness::default();
+    harness.with_fixture_directory("lsp");
+    let query_path = harness.directory_path().join("operator_query.graphql");
+    let query_text = std::fs::read_to_string(&query_path).unwrap();
+    let fragment_path = harness.directory_path().join("user_name_fragment.graphql");
+    let fragment_text = std::fs::read_to_string(&fragment_path).unwrap();
+    let mut client = LspClient::start(&mut harness);
+    client.open(&query_path, &query_text.replace("    nickname\n", ""));
+    assert_eq!(client.diagnostics(&query_path), Vec::<Value>::new());
+    client.open(&fragment_path, &fragment_text);
+    assert_eq!(client.diagnostics(&fragment_path), Vec::<Value>::new());
+    assert_eq!(client.diagnostics(&query_path), Vec::<Value>::new());
+
+    // The importing query is republished with the fragment's unsaved selection
+    client.change(
+        &fragment_path,
+        2,
+        &fragment_text.replace("  lastName\n", "  lastName\n  nickname\n"),
+    );
+    assert_eq!(client.diagnostics(&fragment_path).len(), 1);
+    assert_eq!(client.diagnostics(&query_path), Vec::<Value>::new());
+
+    // Renaming the fragment without saving leaves the query's spread unresolved
+    client.change(
+        &fragment_path,
+        3,
+        &fragment_text.replace("UserNameFragment", "RenamedFragment"),
+    );
+    assert_eq!(client.diagnostics(&fragment_path), Vec::<Value>::new());
+    let diagnostics = client.diagnostics(&query_path);
+    assert!(
+        diagnostics.iter().any(|diagnostic| diagnostic["message"]
+            .as_str()
+            .unwrap()
+            .contains("UserNameFragment")),
+        "{diagnostics:?}"
+    );
+
+    // Closing the fragment resolves the query's import to the contents on disk again
+    client.close(&fragment_path);
+    assert_eq!(client.diagnostics(&fragment_path), Vec::<Value>::new());
+    assert_eq!(client.diagnostics(&query_path), Vec::<Value>::new());
+}
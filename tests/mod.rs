@@ -1,4 +1,8 @@
 mod cli;
 mod helpers;
+mod library;
+mod lsp;
+mod npm;
 mod schema;
 mod typescript;
+mod validation;
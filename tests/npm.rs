@@ -0,0 +1,26 @@
+use assert_cmd::cargo::cargo_bin;
+use std::io::ErrorKind;
+use std::process::Command;
+
+#[test]
+fn npm_api_against_built_binary() {
+    let output = match Command::new("node")
+        .args(["--test", "test/"])
+        .current_dir("pkg/npm")
+        .env("QLC_BINARY", cargo_bin("qlc"))
+        .output()
+    {
+        Ok(output) => output,
+        Err(error) if error.kind() == ErrorKind::NotFound => {
+            eprintln!("skipping the npm API tests, `node` is not installed");
+            return;
+        }
+        Err(error) => panic!("could not run node: {error}"),
+    };
+    assert!(
+        output.status.success(),
+        "npm API tests failed:\n{}{}",
+        String::from_utf8_lossy(&output.stdout),
+        String::from_utf8_lossy(&output.stderr),
+    );
+}
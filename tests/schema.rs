@@ -1,7 +1,10 @@
 use crate::helpers::cmd::TestCommandHarness;
+use crate::helpers::http::StubServer;
 use crate::helpers::stdout_predicates::contains_graphql_file_error_with_location;
 use predicates::prelude::PredicateBooleanExt;
-use predicates::str::contains;
+use predicates::str::{contains, is_empty};
+use std::fs;
+use std::path::Path;
 
 #[test]
 fn compile_with_non_schema_matching_graphql() {
@@ -49,3 +52,381 @@ fn compile_with_narrowing() {
         .run_for_success()
         .stdout(assertion_inline.and(assertion_external));
 }
+
+#[test]
+fn compile_with_custom_root_types() {
+    TestCommandHarness::new()
+        .with_default_schema_file_from_path(Path::new(
+            "tests/fixtures/schema/compile_with_custom_root_types/schema.json",
+        ))
+        .with_fixture_directory("schema/compile_with_custom_root_types")
+        .run_for_success();
+}
+
+#[test]
+fn compile_with_interface_spreads() {
+    TestCommandHarness::new()
+        .with_default_schema_file_from_path(Path::new(
+            "tests/fixtures/schema/compile_with_interface_spreads/schema.json",
+        ))
+        .with_fixture_directory("schema/compile_with_interface_spreads")
+        .run_for_success();
+}
+
+#[test]
+fn compile_with_missing_root_type() {
+    let mut harness = TestCommandHarness::new();
+    let subscription_path = harness.directory_path().join("viewer_subscription.graphql");
+    let assertion = contains("error: schema has no subscription root type")
+        .and(contains("= help: This document is a subscription, but the schema does not declare a root operation type for subscription operations."))
+        .and(contains_graphql_file_error_with_location(
+            &subscription_path,
+            (1, 1),
+        ));
+    harness
+        .with_default_schema_file_from_path(Path::new(
+            "tests/fixtures/schema/compile_with_custom_root_types/schema.json",
+        ))
+        .with_fixture_directory("schema/compile_with_missing_root_type")
+        .run_for_failure()
+        .stdout(assertion);
+}
+
+#[test]
+fn compile_with_missing_query_type() {
+    TestCommandHarness::new()
+        .with_default_schema_file_from_path(Path::new(
+            "tests/fixtures/schema/compile_with_missing_query_type/schema.json",
+        ))
+        .with_fixture_directory("schema/compile_with_missing_query_type")
+        .run_for_success();
+}
+
+#[test]
+fn compile_with_missing_query_type_and_no_query_type() {
+    let mut schema: serde_json::Value = serde_json::from_slice(
+        &fs::read("tests/fixtures/schema/compile_with_custom_root_types/schema.json").unwrap(),
+    )
+    .unwrap();
+    schema["data"]["__schema"]
+        .as_object_mut()
+        .unwrap()
+        .remove("queryType");
+    let mut harness = TestCommandHarness::new();
+    let query_path = harness.directory_path().join("viewer_query.graphql");
+    fs::write(
+        &query_path,
+        "query ViewerQuery {\n  viewer {\n    id\n  }\n}\n",
+    )
+    .unwrap();
+    let assertion = contains("error: schema has no query root type")
+        .and(contains("= help: This document is a query, but the schema's introspection JSON has no `queryType` and no type named `Query`."))
+        .and(contains_graphql_file_error_with_location(&query_path, (1, 1)));
+    harness
+        .with_default_schema_file_from_contents(&schema.to_string())
+        .run_for_failure()
+        .stdout(assertion);
+}
+
+const SCHEMA_JSON_PATH: &str = "tests/fixtures/schema_generation/output/schema.json";
+
+#[test]
+fn fetch_schema() {
+    let schema_json = fs::read(SCHEMA_JSON_PATH).unwrap();
+    let server = StubServer::start(200, schema_json.clone());
+    let mut harness = TestCommandHarness::new();
+    let output_path = harness.directory_path().join("fetched.json");
+    harness
+        .with_env("QLC_SCHEMA_TOKEN", "secret")
+        .with_command(&["schema", "fetch"])
+        .with_arg(server.url())
+        .with_arg("-o")
+        .with_arg(&output_path)
+        .with_arg("-H")
+        .with_arg("X-Client: qlc")
+        .run_for_success()
+        .stdout(is_empty());
+    assert_eq!(fs::read(&output_path).unwrap(), schema_json);
+
+    let request = server.next_request();
+    assert!(request.starts_with("POST /graphql "), "{request}");
+    assert!(
+        request.contains("\r\nAuthorization: Bearer secret\r\n"),
+        "{request}"
+    );
+    assert!(request.contains("\r\nX-Client: qlc\r\n"), "{request}");
+    assert!(request.contains("query IntrospectionQuery"), "{request}");
+}
+
+#[test]
+fn fetch_schema_with_config_file() {
+    let server = StubServer::start(200, fs::read(SCHEMA_JSON_PATH).unwrap());
+    let mut harness = TestCommandHarness::new();
+    let rc_contents = format!(
+        r#"{{ "schemaUrl": "{}", "schemaHeaders": {{ "X-Team": "web" }}, "schemaFile": "schema.json" }}"#,
+        server.url()
+    );
+    harness
+        .with_default_rc_file_contents(&rc_contents)
+        .with_command(&["schema", "fetch"])
+        .with_arg("--header=X-Team: mobile")
+        .run_for_success();
+    // Written to the schema file, relative to the config file, where compiling reads it from
+    assert!(harness.directory_path().join("schema.json").exists());
+
+    let request = server.next_request();
+    assert!(request.contains("\r\nX-Team: mobile\r\n"), "{request}");
+    assert!(!request.contains("web"), "{request}");
+}
+
+#[test]
+fn fetch_schema_with_introspection_errors() {
+    let server = StubServer::start(
+        200,
+        r#"{"errors":[{"message":"introspection is disabled"}]}"#,
+    );
+    let mut harness = TestCommandHarness::new();
+    let output_path = harness.directory_path().join("fetched.json");
+    harness
+        .with_command(&["schema", "fetch"])
+        .with_arg(server.url())
+        .with_arg("-o")
+        .with_arg(&output_path)
+        .run_for_failure()
+        .stdout(contains(format!(
+            "introspection of `{}` failed: introspection is disabled",
+            server.url()
+        )));
+    assert!(!output_path.exists());
+}
+
+#[test]
+fn fetch_schema_with_error_status() {
+    let server = StubServer::start(502, "bad gateway");
+    let mut harness = TestCommandHarness::new();
+    let output_path = harness.directory_path().join("fetched.json");
+    harness
+        .with_command(&["schema", "fetch"])
+        .with_arg(server.url())
+        .with_arg("-o")
+        .with_arg(&output_path)
+        .run_for_failure()
+        .stdout(contains(format!(
+            "could not fetch schema from `{}`: status code 502",
+            server.url()
+        )));
+    assert!(!output_path.exists());
+}
+
+#[test]
+fn fetch_schema_with_error_status_and_introspection_errors() {
+    let server = StubServer::start(
+        400,
+        r#"{"errors":[{"message":"introspection is disabled"}]}"#,
+    );
+    let mut harness = TestCommandHarness::new();
+    let output_path = harness.directory_path().join("fetched.json");
+    harness
+        .with_command(&["schema", "fetch"])
+        .with_arg(server.url())
+        .with_arg("-o")
+        .with_arg(&output_path)
+        .run_for_failure()
+        .stdout(contains(format!(
+            "introspection of `{}` failed: introspection is disabled",
+            server.url()
+        )));
+    assert!(!output_path.exists());
+}
+
+const DIFF_OLD_SCHEMA_PATH: &str = "tests/fixtures/schema/diff_schemas/old_schema.json";
+const DIFF_NEW_SCHEMA_PATH: &str = "tests/fixtures/schema/diff_schemas/new_schema.json";
+
+#[test]
+fn diff_schemas() {
+    let assertion = contains("breaking: field `User.name` changed type from `String!` to `String`")
+        .and(contains("breaking: field `User.legacyId` was removed"))
+        .and(contains(
+            "breaking: enum value `GUEST` was removed from `Role`",
+        ))
+        .and(contains(
+            "breaking: input field `UserFilter.team` of type `ID!` was added",
+        ))
+        .and(contains(
+            "change: field `User.email` changed type from `String` to `String!`",
+        ))
+        .and(contains(
+            "change: argument `Query.search(limit:)` of type `Int` was added",
+        ))
+        .and(contains("change: field `Team.name` was deprecated"))
+        .and(contains(
+            "change: member `Project` was added to `SearchResult`",
+        ))
+        .and(contains("error: 4 breaking schema changes"));
+    TestCommandHarness::new()
+        .with_command(&["schema", "diff"])
+        .with_arg(DIFF_OLD_SCHEMA_PATH)
+        .with_arg(DIFF_NEW_SCHEMA_PATH)
+        .run_for_failure()
+        .stdout(assertion);
+}
+
+#[test]
+fn diff_schemas_without_changes() {
+    TestCommandHarness::new()
+        .with_command(&["schema", "diff"])
+        .with_arg(DIFF_OLD_SCHEMA_PATH)
+        .with_arg(DIFF_OLD_SCHEMA_PATH)
+        .run_for_success()
+        .stdout(is_empty());
+}
+
+#[test]
+fn diff_schemas_with_documents() {
+    let mut harness = TestCommandHarness::new();
+    let dir_path = harness.directory_path().to_owned();
+    let assertion = contains("error: unknown field `legacyId`")
+        .and(contains_graphql_file_error_with_location(
+            dir_path.join("legacy_query.graphql"),
+            (4, 5),
+        ))
+        .and(contains(format!(
+            "warning: generated types change with the new schema\n  --> {}",
+            dir_path.join("name_query.graphql").display()
+        )))
+        .and(contains(
+            "warning: generated types of the `graphql-globals` module change with the new schema",
+        ))
+        .and(contains("role_query.graphql").not());
+    harness
+        .with_fixture_directory("schema/diff_schemas")
+        .with_command(&["schema", "diff"])
+        .with_arg(DIFF_OLD_SCHEMA_PATH)
+        .with_arg(DIFF_NEW_SCHEMA_PATH)
+        .with_arg("--documents")
+        .with_arg(&dir_path)
+        .run_for_failure()
+        .stdout(assertion);
+    // Nothing is written while comparing
+    assert!(!dir_path.join("__generated__").exists());
+}
+
+const PRINT_SCHEMA_PATH: &str = "tests/fixtures/schema/print_schema/schema.json";
+
+fn expected_sdl() -> String {
+    std::fs::read_to_string("tests/fixtures/schema/print_schema/schema.graphql").unwrap()
+}
+
+#[test]
+fn compile_with_incomplete_directive_in_schema() {
+    let mut schema: serde_json::Value =
+        serde_json::from_slice(&fs::read(SCHEMA_JSON_PATH).unwrap()).unwrap();
+    schema["data"]["__schema"]["directives"]
+        .as_array_mut()
+        .unwrap()
+        .push(serde_json::json!({ "name": "legacy", "description": null }));
+    let mut harness = TestCommandHarness::new();
+    fs::write(
+        harness.directory_path().join("operator_query.graphql"),
+        "query Operator {\n  operator {\n    id\n  }\n}\n",
+    )
+    .unwrap();
+    harness
+        .with_default_schema_file_from_contents(&schema.to_string())
+        .run_for_success()
+        .stdout(is_empty());
+}
+
+#[test]
+fn print_schema() {
+    TestCommandHarness::new()
+        .with_command(&["schema", "print"])
+        .with_arg(PRINT_SCHEMA_PATH)
+        .run_for_success()
+        .stdout(expected_sdl());
+}
+
+#[test]
+fn print_schema_with_compile_option() {
+    TestCommandHarness::new()
+        .with_command(&["schema", "print"])
+        .with_arg(PRINT_SCHEMA_PATH)
+        .with_arg("--add-typename")
+        .run()
+        .failure()
+        .stdout(is_empty())
+        .stderr(contains("unexpected argument '--add-typename' found"));
+}
+
+const PROCESSED_SCHEMA_FIXTURE_PATH: &str = "tests/fixtures/schema/print_schema/schema.bin";
+
+fn processed_schema_entries(cache_dir: &Path) -> Vec<std::path::PathBuf> {
+    fs::read_dir(cache_dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|extension| extension == "bin"))
+        .collect()
+}
+
+/// The fixture is the processed schema of the print schema fixture. Once `Schema` changes shape it
+/// no longer reads back, and `PROCESSED_SCHEMA_FORMAT_VERSION` has to be bumped before the fixture
+/// is written again with `OVERWRITE_FIXTURES`.
+#[test]
+fn print_schema_from_processed_schema() {
+    if std::env::var_os("OVERWRITE_FIXTURES").is_some() {
+        let mut harness = TestCommandHarness::new();
+        let cache_dir = harness.directory_path().join(".qlc-cache");
+        harness
+            .with_command(&["schema", "print"])
+            .with_arg("--schema-path")
+            .with_arg(PRINT_SCHEMA_PATH)
+            .with_arg("--cache-dir")
+            .with_arg(&cache_dir)
+            .run_for_success();
+        fs::copy(
+            &processed_schema_entries(&cache_dir)[0],
+            PROCESSED_SCHEMA_FIXTURE_PATH,
+        )
+        .unwrap();
+    }
+
+    let mut harness = TestCommandHarness::default();
+    let cache_dir = harness.directory_path().join(".qlc-cache");
+    let schema_path = harness.directory_path().join("schema.json");
+    harness
+        .with_command(&["schema", "print"])
+        .with_arg("--schema-path")
+        .with_arg(schema_path)
+        .with_arg("--cache-dir")
+        .with_arg(&cache_dir);
+    harness.run_for_success();
+    let entries = processed_schema_entries(&cache_dir);
+    assert_eq!(entries.len(), 1);
+
+    // Standing in for the processed default schema, the fixture is what gets printed when it reads
+    fs::copy(PROCESSED_SCHEMA_FIXTURE_PATH, &entries[0]).unwrap();
+    let output = harness.run_for_success().get_output().stdout.clone();
+    assert!(
+        String::from_utf8(output).unwrap() == expected_sdl(),
+        "the processed schema fixture no longer reads, bump `PROCESSED_SCHEMA_FORMAT_VERSION` and \
+         rerun with `OVERWRITE_FIXTURES=1`"
+    );
+}
+
+#[test]
+fn print_schema_to_file() {
+    let mut harness = TestCommandHarness::new();
+    let output_path = harness.directory_path().join("schema.graphql");
+    harness
+        .with_arg("--schema-path")
+        .with_arg(PRINT_SCHEMA_PATH)
+        .with_command(&["schema", "print"])
+        .with_arg("-o")
+        .with_arg(&output_path)
+        .run_for_success()
+        .stdout(is_empty());
+    assert_eq!(
+        std::fs::read_to_string(output_path).unwrap(),
+        expected_sdl()
+    );
+}
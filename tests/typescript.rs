@@ -40,6 +40,14 @@ fn compile_typename() {
         .run_for_success();
 }
 
+#[test]
+fn compile_with_add_typename() {
+    TestCommandHarness::default()
+        .with_arg("--add-typename")
+        .with_fixture_directory("typescript/compile_with_add_typename")
+        .run_for_success();
+}
+
 #[test]
 fn compile_with_all_module_config() {
     TestCommandHarness::default()
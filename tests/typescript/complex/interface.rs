@@ -29,3 +29,13 @@ fn compile_interface_both_concrete_and_abstract() {
         )
         .run_for_success();
 }
+
+#[test]
+fn compile_interface_with_exhaustive_typename_unions() {
+    TestCommandHarness::default()
+        .with_arg("--exhaustive-typename-unions")
+        .with_fixture_directory(
+            "typescript/complex/interface/compile_interface_with_exhaustive_typename_unions",
+        )
+        .run_for_success();
+}
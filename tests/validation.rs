@@ -0,0 +1,179 @@
+use crate::helpers::cmd::TestCommandHarness;
+use crate::helpers::stdout_predicates::contains_graphql_file_error_with_location;
+use predicates::prelude::PredicateBooleanExt;
+use predicates::str::contains;
+
+#[test]
+fn compile_with_impossible_spreads() {
+    let mut harness = TestCommandHarness::default();
+    let impossible_query_path = harness.directory_path().join("impossible_query.graphql");
+
+    let assertion_external = contains("error: impossible fragment spread with type `Host`")
+        .and(contains("= help: A value of type `User` can never be a `Host`, so this spread would never apply."))
+        .and(contains_graphql_file_error_with_location(
+            &impossible_query_path,
+            (6, 8),
+        ));
+    let assertion_inline = contains("error: impossible fragment spread with type `Network`")
+        .and(contains("7 |     ... on Network {\n  |         ^"))
+        .and(contains_graphql_file_error_with_location(
+            &impossible_query_path,
+            (7, 9),
+        ));
+
+    harness
+        .with_arg("--strict-validation")
+        .with_fixture_directory("validation/compile_with_impossible_spreads")
+        .run_for_failure()
+        .stdout(assertion_external.and(assertion_inline));
+}
+
+#[test]
+fn compile_with_impossible_spreads_without_strict_validation() {
+    let assertion = contains("error: impossible fragment spread")
+        .not()
+        .and(contains("making spreading `Host` extraneous."))
+        .and(contains("making spreading `Network` extraneous."));
+
+    TestCommandHarness::default()
+        .with_fixture_directory("validation/compile_with_impossible_spreads")
+        .run_for_success()
+        .stdout(assertion);
+}
+
+#[test]
+fn compile_with_fragment_cycle() {
+    let mut harness = TestCommandHarness::default();
+    let cycle_query_path = harness.directory_path().join("cycle_query.graphql");
+
+    let assertion = contains("error: fragment spread forms a cycle")
+        .and(contains(
+            "= help: Fragments cannot spread themselves, but this spread leads to `CycleA` -> `CycleB` -> `CycleA`.",
+        ))
+        .and(contains_graphql_file_error_with_location(
+            &cycle_query_path,
+            (6, 8),
+        ));
+
+    harness
+        .with_fixture_directory("validation/compile_with_fragment_cycle")
+        .run_for_failure()
+        .stdout(assertion);
+}
+
+#[test]
+fn compile_with_invalid_arguments_and_directives() {
+    let assertion = contains("error: misplaced directive `@skip`")
+        .and(contains(
+            "= help: This directive cannot be used on a query.",
+        ))
+        .and(contains("error: duplicate argument `id`"))
+        .and(contains("2 |   host(id: $id, id: $id) {\n  |   ^"))
+        .and(contains("error: duplicate directive `@include`"))
+        .and(contains(
+            "3 |     id @include(if: $flag) @include(if: $flag)\n  |                            ^",
+        ));
+
+    TestCommandHarness::default()
+        .with_fixture_directory("validation/compile_with_invalid_arguments_and_directives")
+        .run_for_failure()
+        .stdout(assertion);
+}
+
+const SCHEMA_JSON_PATH: &str = "tests/fixtures/schema_generation/output/schema.json";
+
+fn schema_with_directives(directives: serde_json::Value) -> String {
+    let mut schema: serde_json::Value =
+        serde_json::from_slice(&std::fs::read(SCHEMA_JSON_PATH).unwrap()).unwrap();
+    schema["data"]["__schema"]["directives"] = directives;
+    schema.to_string()
+}
+
+#[test]
+fn compile_with_schema_directive_locations() {
+    let schema = schema_with_directives(serde_json::json!([
+        { "name": "cached", "locations": ["QUERY"], "args": [], "isRepeatable": false },
+        { "name": "tag", "locations": ["FIELD"], "args": [], "isRepeatable": true },
+    ]));
+    let mut harness = TestCommandHarness::new();
+    let query_path = harness.directory_path().join("cached_query.graphql");
+    std::fs::write(
+        &query_path,
+        "query CachedQuery @cached {\n  operator @cached @tag @tag {\n    id\n  }\n}\n",
+    )
+    .unwrap();
+    let assertion = contains("error: misplaced directive `@cached`")
+        .and(contains(
+            "= help: This directive cannot be used on a field.",
+        ))
+        .and(contains_graphql_file_error_with_location(
+            &query_path,
+            (2, 12),
+        ))
+        .and(contains("duplicate directive").not())
+        .and(contains("failure due to 1 error"));
+    harness
+        .with_default_schema_file_from_contents(&schema)
+        .run_for_failure()
+        .stdout(assertion);
+}
+
+#[test]
+fn compile_with_built_in_directive_locations() {
+    // The spec's directives are still checked when the introspection JSON has none
+    let schema = schema_with_directives(serde_json::Value::Null);
+    let mut harness = TestCommandHarness::new();
+    let query_path = harness.directory_path().join("skipped_query.graphql");
+    std::fs::write(
+        &query_path,
+        "query SkippedQuery @skip(if: true) {\n  operator {\n    id\n  }\n}\n",
+    )
+    .unwrap();
+    let assertion = contains("error: misplaced directive `@skip`")
+        .and(contains(
+            "= help: This directive cannot be used on a query.",
+        ))
+        .and(contains_graphql_file_error_with_location(
+            &query_path,
+            (1, 20),
+        ));
+    harness
+        .with_default_schema_file_from_contents(&schema)
+        .run_for_failure()
+        .stdout(assertion);
+}
+
+#[test]
+fn compile_with_conflicting_fields() {
+    let assertion = contains("error: conflicting selections for `name`")
+        .and(contains("= help: `firstName` and `lastName` are different fields. Use different aliases to select both."))
+        .and(contains("error: conflicting selections for `host`"))
+        .and(contains("= help: They have differing arguments. Use different aliases to select both."));
+
+    TestCommandHarness::default()
+        .with_fixture_directory("validation/compile_with_conflicting_fields")
+        .run_for_failure()
+        .stdout(assertion);
+}
+
+#[test]
+fn compile_with_undefined_and_unused_variables() {
+    let assertion = contains("error: undefined variable `$missing`")
+        .and(contains("error: unused variable `$unused`"))
+        .and(contains(
+            "1 | query Variables($id: ID!, $unused: Int) {\n  |                           ^",
+        ));
+
+    TestCommandHarness::default()
+        .with_arg("--strict-validation")
+        .with_fixture_directory("validation/compile_with_undefined_and_unused_variables")
+        .run_for_failure()
+        .stdout(assertion);
+}
+
+#[test]
+fn compile_with_valid_directives() {
+    TestCommandHarness::default()
+        .with_fixture_directory("validation/compile_with_valid_directives")
+        .run_for_success();
+}